@@ -0,0 +1,33 @@
+//! Embeds the commit and date of the build, which `--version` reports next to the crate version.
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let commit: String = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty())
+        .unwrap_or_else(|| "unknown".into());
+
+    // `SOURCE_DATE_EPOCH` pins the date, for builds that have to be reproducible.
+    let now: jiff::Timestamp = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|secs| secs.parse::<i64>().ok())
+        .and_then(|secs| jiff::Timestamp::from_second(secs).ok())
+        .unwrap_or_else(jiff::Timestamp::now);
+
+    println!("cargo:rustc-env=RFM_COMMIT={}", commit);
+    println!("cargo:rustc-env=RFM_BUILD_DATE={}", now.strftime("%Y-%m-%d"));
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    // A path that doesn't exist would rerun this on every build, so only watch what's there.
+    for path in [".git/HEAD", ".git/refs", ".git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
@@ -0,0 +1,301 @@
+//! Unpacking downloaded archives for `--extract`.
+
+use crate::error::RfmError;
+use crate::install::InstallOptions;
+use ansi_term::Colour::*;
+use crate::output::paint;
+use flate2::read::GzDecoder;
+use std::fs::{create_dir_all, hard_link, remove_file, File, Permissions};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use tar::EntryType;
+
+/// The archive formats `extract()` unpacks.
+pub(crate) const FORMATS: [&str; 2] = ["tar", "zip"];
+
+/// Unpacks a downloaded archive into the directory it was saved in.
+pub(crate) fn extract(
+    archive: &Path,
+    opts: &InstallOptions,
+) -> Result<(), RfmError> {
+    let into: &Path = match archive.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    // Trust the content over the name; zip is recognised by its magic alone.
+    let mut magic: [u8; 4] = [0u8; 4];
+    let read: usize = File::open(archive)?.read(&mut magic)?;
+    let is_zip: bool = read == 4 && magic == *b"PK\x03\x04";
+    let is_gzip: bool = read >= 2 && magic[..2] == [0x1f, 0x8b];
+    let name: String = archive.to_string_lossy().to_lowercase();
+
+    let count: usize = if is_zip {
+        extract_zip(archive, into, opts.strip_components)?
+    } else if is_gzip || name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        extract_tar_gz(archive, into, opts.strip_components)?
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} is not a supported archive ({})",
+                archive.display(),
+                FORMATS.join(", ")
+            ),
+        )
+        .into());
+    };
+
+    say!(
+        "{}: Extracted {} entries into {}",
+        paint(Green, "Success"),
+        count,
+        paint(Yellow, into.display().to_string())
+    );
+
+    if !opts.keep_archive {
+        remove_file(archive)?;
+    }
+
+    Ok(())
+}
+
+/// Maps an archive entry name onto a path relative to the extraction root, dropping
+/// `strip` leading components. Returns `None` for entries stripped away entirely and
+/// refuses absolute paths or `..` components that would land outside the root.
+fn sanitize_entry_path(name: &Path, strip: usize) -> io::Result<Option<PathBuf>> {
+    let mut parts: Vec<&std::ffi::OsStr> = Vec::new();
+
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => parts.push(part),
+            Component::CurDir => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "refusing to extract {}: it escapes the target directory",
+                        name.display()
+                    ),
+                ));
+            }
+        }
+    }
+
+    if parts.len() <= strip {
+        return Ok(None);
+    }
+    Ok(Some(parts[strip..].iter().collect()))
+}
+
+/// Whether a link stored at `entry` (relative to the root) pointing at `target` resolves outside the root.
+fn link_escapes(entry: &Path, target: &Path) -> bool {
+    if target.is_absolute() {
+        return true;
+    }
+
+    // Walk the target lexically from the link's own directory, tracking depth below the root.
+    let mut depth: usize = entry.components().count().saturating_sub(1);
+    for component in target.components() {
+        match component {
+            Component::ParentDir if depth == 0 => return true,
+            Component::ParentDir => depth -= 1,
+            Component::Normal(_) => depth += 1,
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// Refuses `relative` if any directory on its way down from `into` is a symlink, which would
+/// have the entry written wherever that link leads. Links that each stay inside on their own
+/// can still chain their way out, so being on the path at all is enough.
+fn check_no_link_on_path(into: &Path, relative: &Path) -> io::Result<()> {
+    let mut path: PathBuf = into.to_path_buf();
+    for part in relative.parent().into_iter().flat_map(Path::components) {
+        path.push(part);
+        if path.symlink_metadata().is_ok_and(|meta| meta.is_symlink()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "refusing to extract {}: {} is a symlink",
+                    relative.display(),
+                    path.display()
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn extract_tar_gz(archive: &Path, into: &Path, strip: usize) -> io::Result<usize> {
+    let mut tar = tar::Archive::new(GzDecoder::new(File::open(archive)?));
+    let mut count: usize = 0;
+
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let Some(relative) = sanitize_entry_path(&entry.path()?, strip)? else {
+            continue;
+        };
+
+        // A symlink pointing outside would let a later entry write through it.
+        if let Some(link) = entry.link_name()?
+            && link_escapes(&relative, &link)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "refusing to extract {}: its link points outside the target directory",
+                    relative.display()
+                ),
+            ));
+        }
+
+        check_no_link_on_path(into, &relative)?;
+        let target: PathBuf = into.join(&relative);
+        if let Some(parent) = target.parent() {
+            create_dir_all(parent)?;
+        }
+        // Replace a link already at the target rather than write through it.
+        if target.symlink_metadata().is_ok_and(|meta| meta.is_symlink()) {
+            remove_file(&target)?;
+        }
+
+        // A hard link names another entry of the archive, so it resolves under `into` too,
+        // stripped the same way; `unpack()` would look for it relative to the working directory.
+        if entry.header().entry_type() == EntryType::Link {
+            let link: PathBuf = entry.link_name()?.map(PathBuf::from).unwrap_or_default();
+            let Some(source) = sanitize_entry_path(&link, strip)? else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("refusing to extract {}: its link is stripped", relative.display()),
+                ));
+            };
+            check_no_link_on_path(into, &source)?;
+            if target.exists() {
+                remove_file(&target)?;
+            }
+            hard_link(into.join(&source), &target)?;
+        } else {
+            entry.unpack(&target)?;
+        }
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+fn extract_zip(archive: &Path, into: &Path, strip: usize) -> io::Result<usize> {
+    let mut zip = zip::ZipArchive::new(File::open(archive)?).map_err(io::Error::other)?;
+    let mut count: usize = 0;
+
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(io::Error::other)?;
+        let name: PathBuf = PathBuf::from(entry.name().map_err(io::Error::other)?.as_ref());
+        let Some(relative) = sanitize_entry_path(&name, strip)? else {
+            continue;
+        };
+
+        // Links are skipped outright rather than risk one pointing outside the target.
+        if entry.is_symlink() {
+            notice!(
+                "{} skipping symlink {}",
+                paint(Yellow, "Warning:"),
+                relative.display()
+            );
+            continue;
+        }
+
+        let target: PathBuf = into.join(&relative);
+        if entry.is_dir() {
+            create_dir_all(&target)?;
+        } else {
+            if let Some(parent) = target.parent() {
+                create_dir_all(parent)?;
+            }
+            io::copy(&mut entry, &mut File::create(&target)?)?;
+        }
+
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&target, Permissions::from_mode(mode & 0o7777))?;
+        }
+
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use tar::{Builder, Header};
+
+    /// An empty directory of its own under the system temp directory.
+    fn scratch(name: &str) -> PathBuf {
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("rfm-archive-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes a `.tar.gz` at `path` holding `entries` of (path, type, link target, contents).
+    fn write_archive(path: &Path, entries: &[(&str, EntryType, &str, &[u8])]) {
+        let gzip: GzEncoder<File> = GzEncoder::new(File::create(path).unwrap(), Compression::fast());
+        let mut builder: Builder<GzEncoder<File>> = Builder::new(gzip);
+        for (name, kind, link, data) in entries {
+            let mut header: Header = Header::new_gnu();
+            header.set_entry_type(*kind);
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            // Literal, so the tests can hold the `..` a hostile archive would.
+            header.set_link_name_literal(link).unwrap();
+            builder.append_data(&mut header, name, *data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_a_symlink_chain_out_of_the_target() {
+        let dir: PathBuf = scratch("chain");
+        let into: PathBuf = dir.join("into");
+        create_dir_all(&into).unwrap();
+        let archive: PathBuf = dir.join("evil.tar.gz");
+        // Each link stays inside on its own terms, but together `l2` resolves to `dir`.
+        write_archive(&archive, &[
+            ("sub/l1", EntryType::Symlink, ".", b""),
+            ("sub/l1/l1/l2", EntryType::Symlink, "../..", b""),
+            ("sub/l1/l1/l2/escaped", EntryType::Regular, "", b"outside"),
+        ]);
+
+        let result: io::Result<usize> = extract_tar_gz(&archive, &into, 0);
+        assert!(result.unwrap_err().to_string().contains("is a symlink"));
+        assert!(!dir.join("escaped").exists());
+        assert!(!dir.join("sub").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resolves_hard_links_in_the_target() {
+        let dir: PathBuf = scratch("hardlink");
+        let into: PathBuf = dir.join("into");
+        create_dir_all(&into).unwrap();
+        let archive: PathBuf = dir.join("links.tar.gz");
+        write_archive(&archive, &[
+            ("top/file", EntryType::Regular, "", b"contents"),
+            ("top/link", EntryType::Link, "top/file", b""),
+        ]);
+
+        // From the working directory, `top/file` doesn't exist; only `into` has it.
+        assert_eq!(extract_tar_gz(&archive, &into, 1).unwrap(), 2);
+        assert_eq!(std::fs::read(into.join("link")).unwrap(), b"contents");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
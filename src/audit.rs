@@ -0,0 +1,102 @@
+//! The `--log-file` audit trail: one JSON line per operation, appended to a file whatever the
+//! terminal output looks like, so there is a lasting record of what rfm did.
+
+use serde::Serialize;
+use std::cell::Cell;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// One line of the audit log:
+///
+/// ```json
+/// {"time":"2024-05-01T09:30:00.5Z","mode":"delete","paths":["/srv/old"],"urls":[],
+///  "move_to":null,"dry_run":false,"bytes":0,"result":"success","error":null}
+/// ```
+#[derive(Serialize)]
+pub struct AuditEntry<'a> {
+    /// "install", "delete", "move", "copy", "hash" or "doctor"; absent when no single mode was named.
+    pub mode: Option<&'static str>,
+    /// The paths operated on, made absolute; see `resolve()`.
+    pub paths: Vec<String>,
+    pub urls: &'a [String],
+    /// The destination of a move or copy, made absolute the same way.
+    pub move_to: Option<String>,
+    pub dry_run: bool,
+    /// Bytes downloaded; zero for every other mode.
+    pub bytes: u64,
+    /// "started" just before a delete, then "success", "error", or "declined" when the user
+    /// answered no to a confirmation.
+    pub result: &'static str,
+    pub error: Option<String>,
+}
+
+/// The entry with the time it was written, which is what goes into the file.
+#[derive(Serialize)]
+struct Line<'a> {
+    time: String,
+    #[serde(flatten)]
+    entry: &'a AuditEntry<'a>,
+}
+
+/// The open log file; `None` unless `--log-file` was given.
+static LOG: Mutex<Option<File>> = Mutex::new(None);
+
+thread_local! {
+    /// Set when the operation running on this thread stopped at a declined confirmation.
+    static DECLINED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Opens `path` for appending, creating it if needed, and records every following operation.
+pub fn open(path: &Path) -> io::Result<()> {
+    let file: File = OpenOptions::new().create(true).append(true).open(path)?;
+    *LOG.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Whether `--log-file` is recording.
+pub fn enabled() -> bool {
+    LOG.lock().unwrap().is_some()
+}
+
+/// Appends `entry` as one line. With `sync` the line is on disk before this returns, which is
+/// what a delete waits for so the record survives a crash halfway through.
+pub fn record(entry: &AuditEntry, sync: bool) -> io::Result<()> {
+    let mut log = LOG.lock().unwrap();
+    let Some(file) = log.as_mut() else { return Ok(()) };
+    let line: Line = Line { time: jiff::Timestamp::now().to_string(), entry };
+    let mut text: String = serde_json::to_string(&line).map_err(io::Error::from)?;
+    text.push('\n');
+    // A single write keeps lines whole when several rfm processes share one log.
+    file.write_all(text.as_bytes())?;
+    if sync {
+        file.sync_data()?;
+    }
+    Ok(())
+}
+
+/// Notes that the current operation was declined, for the entry recorded once it returns.
+pub fn mark_declined() {
+    DECLINED.with(|declined| declined.set(true));
+}
+
+/// Whether the operation that just returned on this thread was declined, clearing the note.
+pub fn take_declined() -> bool {
+    DECLINED.with(|declined| declined.replace(false))
+}
+
+/// `path` made absolute through its canonical parent directory. The last component is kept as
+/// given, so a symlink is recorded as the link rather than its target and a path that is gone
+/// after the operation still resolves.
+pub fn resolve(path: &str) -> String {
+    let given: &Path = Path::new(path);
+    let resolved: Option<PathBuf> = match (given.parent(), given.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent: &Path = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+            parent.canonicalize().ok().map(|parent| parent.join(name))
+        }
+        _ => given.canonicalize().ok(),
+    };
+    resolved.map_or_else(|| path.to_string(), |path| path.display().to_string())
+}
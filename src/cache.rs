@@ -0,0 +1,76 @@
+//! The `.rfm-cache` sidecar kept next to a download: the validators the server sent with the
+//! file, so the next install of the same URL can ask whether it changed before fetching it.
+
+use log::debug;
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+use std::fs::{read_to_string, remove_file, write};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What identifies the version of a file a server sent.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CacheEntry {
+    /// URL the file was requested from, before redirects.
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// `<dest>.rfm-cache`.
+fn sidecar_path(dest: &Path) -> PathBuf {
+    let mut sidecar = dest.as_os_str().to_owned();
+    sidecar.push(".rfm-cache");
+    PathBuf::from(sidecar)
+}
+
+impl CacheEntry {
+    /// The validators in `response` to `url`, or `None` if the server sent neither.
+    pub(crate) fn from_response(url: &str, response: &Response) -> Option<Self> {
+        let header = |name| Some(response.headers().get(name)?.to_str().ok()?.to_string());
+        let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        Some(CacheEntry { url: url.to_string(), etag, last_modified })
+    }
+
+    /// The entry saved for `dest`. A missing or unreadable sidecar just means there is
+    /// nothing to ask the server about.
+    pub(crate) fn load(dest: &Path) -> Option<Self> {
+        let text: String = read_to_string(sidecar_path(dest)).ok()?;
+        serde_json::from_str(&text)
+            .inspect_err(|e| debug!("ignoring cache for {}: {}", dest.display(), e))
+            .ok()
+    }
+
+    /// Whether these validators describe the file `url` serves; another server's ETag and
+    /// date say nothing about its copy.
+    pub(crate) fn is_for(&self, url: &str) -> bool {
+        self.url == url
+    }
+
+    /// Saves `entry` as the sidecar of `dest`, or removes a stale one when there is none.
+    pub(crate) fn store(dest: &Path, entry: Option<&Self>) -> io::Result<()> {
+        let sidecar: PathBuf = sidecar_path(dest);
+        match entry {
+            Some(entry) => write(&sidecar, serde_json::to_string(entry)?),
+            None => match remove_file(&sidecar) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// Makes `request` conditional, so an unchanged file is answered with 304 Not Modified.
+    pub(crate) fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
@@ -0,0 +1,179 @@
+//! The digests a download can be verified against, and `--hash` mode, which prints them.
+
+use crate::CHUNK_SIZE;
+use crate::error::RfmError;
+use crate::output::{bytes_bar, suspend_progress};
+use indicatif::ProgressBar;
+use sha2::digest::DynDigest;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// A hash algorithm a download can be checked with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Name used in messages, e.g. "SHA-256".
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "MD5",
+            HashAlgorithm::Sha1 => "SHA-1",
+            HashAlgorithm::Sha256 => "SHA-256",
+        }
+    }
+
+    /// Length of a digest written out in hex.
+    pub fn hex_len(self) -> usize {
+        match self {
+            HashAlgorithm::Md5 => 32,
+            HashAlgorithm::Sha1 => 40,
+            HashAlgorithm::Sha256 => 64,
+        }
+    }
+
+    /// Parses a name such as `sha256`, `SHA-256`, `sha1` or `md5`.
+    pub fn from_name(raw: &str) -> Result<Self, String> {
+        let name: String = raw.replace('-', "");
+        [HashAlgorithm::Md5, HashAlgorithm::Sha1, HashAlgorithm::Sha256]
+            .into_iter()
+            .find(|algorithm| algorithm.name().replace('-', "").eq_ignore_ascii_case(&name))
+            .ok_or_else(|| {
+                format!("unknown hash algorithm {:?}, expected sha256, sha1 or md5", raw)
+            })
+    }
+
+    /// The algorithm whose hex digests are `len` characters long.
+    fn from_hex_len(len: usize) -> Option<Self> {
+        [HashAlgorithm::Md5, HashAlgorithm::Sha1, HashAlgorithm::Sha256]
+            .into_iter()
+            .find(|algorithm| algorithm.hex_len() == len)
+    }
+
+    /// Whether collisions can be made on purpose, so a match only rules out accidental damage.
+    pub fn is_weak(self) -> bool {
+        self != HashAlgorithm::Sha256
+    }
+
+    /// A fresh hasher for this algorithm.
+    pub(crate) fn hasher(self) -> Box<dyn DynDigest + Send> {
+        match self {
+            HashAlgorithm::Md5 => Box::new(md5::Md5::default()),
+            HashAlgorithm::Sha1 => Box::new(sha1::Sha1::default()),
+            HashAlgorithm::Sha256 => Box::new(sha2::Sha256::default()),
+        }
+    }
+}
+
+/// A hex digest the downloaded bytes must hash to.
+#[derive(Clone, Copy, Debug)]
+pub struct Checksum<'a> {
+    pub algorithm: HashAlgorithm,
+    pub expected: &'a str,
+}
+
+impl Checksum<'_> {
+    /// Compares the hex digest of what was downloaded with the expected one, ignoring case.
+    pub(crate) fn verify(&self, actual: &str) -> Result<(), RfmError> {
+        if self.expected.eq_ignore_ascii_case(actual) {
+            return Ok(());
+        }
+        Err(RfmError::ChecksumMismatch {
+            algorithm: self.algorithm.name(),
+            expected: self.expected.to_string(),
+            actual: actual.to_string(),
+        })
+    }
+}
+
+/// Lower-case hex of a finished digest.
+pub(crate) fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// One line of a checksum file: the digest, its algorithm and the file it is for, if named.
+fn parse_checksum_line(line: &str) -> Result<(HashAlgorithm, &str, Option<&str>), String> {
+    // BSD style: `SHA256 (tool.tar.gz) = <hex>`.
+    let (tag, digest, name): (Option<&str>, &str, Option<&str>) =
+        match line.split_once(" (").zip(line.rsplit_once(") = ")) {
+            Some(((tag, _), (head, digest))) => {
+                (Some(tag), digest.trim(), head.split_once(" (").map(|(_, name)| name))
+            }
+            // GNU style: `<hex>  tool.tar.gz`, with `*` before the name in binary mode, or a
+            // bare digest with no name at all.
+            None => match line.split_once(char::is_whitespace) {
+                Some((digest, rest)) => {
+                    let name: &str = rest.strip_prefix([' ', '*']).unwrap_or(rest);
+                    (None, digest, Some(name))
+                }
+                None => (None, line, None),
+            },
+        };
+
+    let algorithm: HashAlgorithm = HashAlgorithm::from_hex_len(digest.len())
+        .filter(|_| digest.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| format!("{:?} is not a hex digest", digest))?;
+    if let Some(tag) = tag
+        && !tag.replace('-', "").eq_ignore_ascii_case(&algorithm.name().replace('-', ""))
+    {
+        return Err(format!("a {} digest is labelled {}", algorithm.name(), tag));
+    }
+
+    Ok((algorithm, digest, name))
+}
+
+/// Finds the digest for the file called `name` in the text of a `sha256sum`- or BSD-style
+/// checksum file. A file with a single unnamed digest applies to whatever was downloaded.
+pub fn parse_checksum_file(text: &str, name: &str) -> Result<(HashAlgorithm, String), String> {
+    let mut listed: Vec<&str> = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let (algorithm, digest, entry) = parse_checksum_line(line)?;
+        // Entries may carry the directory they were generated in.
+        match entry.map(|entry| entry.rsplit(['/', '\\']).next().unwrap_or(entry)) {
+            None => return Ok((algorithm, digest.to_string())),
+            Some(entry) if entry == name => return Ok((algorithm, digest.to_string())),
+            Some(entry) => listed.push(entry),
+        }
+    }
+
+    match listed.as_slice() {
+        [] => Err("no checksums found".into()),
+        [other] => Err(format!("it is for {}, not {}", other, name)),
+        _ => Err(format!("none of its {} entries is for {}", listed.len(), name)),
+    }
+}
+
+/// Hashes the whole file at `path` with `algorithm`, returning the hex digest.
+pub(crate) fn hash_file(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut file: File = File::open(path)?;
+    let mut hasher: Box<dyn DynDigest + Send> = algorithm.hasher();
+    let mut buffer: [u8; CHUNK_SIZE] = [0; CHUNK_SIZE];
+    // Reading a big file takes a while, so show how far along it is.
+    let pb: ProgressBar = bytes_bar(file.metadata()?.len());
+    loop {
+        let n: usize = file.read(&mut buffer).inspect_err(|_| pb.finish_and_clear())?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        pb.inc(n as u64);
+    }
+    pb.finish_and_clear();
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// Prints the digest of the file at `path` as a `sha256sum`-style `<hex>  <path>` line, so the
+/// output can be saved as a checksum file.
+pub fn print_digest(path: &str, algorithm: HashAlgorithm) -> Result<(), RfmError> {
+    if Path::new(path).is_dir() {
+        return Err(RfmError::Io(io::Error::new(io::ErrorKind::IsADirectory, "is a directory")));
+    }
+    let digest: String = hash_file(Path::new(path), algorithm)?;
+    // The digests are the output itself, so unlike messages they are printed even with --quiet.
+    suspend_progress(|| println!("{}  {}", digest, path));
+    Ok(())
+}
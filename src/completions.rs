@@ -0,0 +1,144 @@
+//! Shell completion scripts built from the flags argh knows about, for `--completions`.
+
+use argh::{CommandInfoWithArgs, FlagInfo, FlagInfoKind, Optionality};
+
+/// Shells `--completions` can write a script for.
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// Returns the completion script for `shell`, or an error naming the supported shells.
+/// `name` is the command the script completes, since argh only knows the struct's name.
+pub fn generate(shell: &str, name: &str, info: &CommandInfoWithArgs) -> Result<String, String> {
+    // Hidden flags stay out of the scripts just as they stay out of `--help`.
+    let flags: Vec<&FlagInfo> = info.flags.iter().filter(|flag| !flag.hidden).collect();
+    match shell {
+        "bash" => Ok(bash(name, &flags)),
+        "zsh" => Ok(zsh(name, &flags)),
+        "fish" => Ok(fish(name, &flags)),
+        _ => Err(format!("unsupported shell {:?}, expected one of: {}", shell, SHELLS.join(", "))),
+    }
+}
+
+/// The flag's long name without its leading dashes.
+fn long_name<'a>(flag: &FlagInfo<'a>) -> &'a str {
+    flag.long.trim_start_matches('-')
+}
+
+fn takes_value(flag: &FlagInfo) -> bool {
+    matches!(flag.kind, FlagInfoKind::Option { .. })
+}
+
+fn bash(name: &str, flags: &[&FlagInfo]) -> String {
+    let mut words: Vec<String> = Vec::new();
+    let mut valued: Vec<&str> = Vec::new();
+    for flag in flags {
+        words.push(flag.long.to_string());
+        if let Some(short) = flag.short {
+            words.push(format!("-{}", short));
+        }
+        if takes_value(flag) {
+            valued.push(flag.long);
+        }
+    }
+
+    format!(
+        r#"_{name}() {{
+    local cur prev
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+
+    case "$prev" in
+        {valued})
+            COMPREPLY=($(compgen -f -- "$cur"))
+            return
+            ;;
+    esac
+
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "{words}" -- "$cur"))
+    else
+        COMPREPLY=($(compgen -f -- "$cur"))
+    fi
+}}
+
+complete -o filenames -F _{name} {name}
+"#,
+        name = name,
+        valued = valued.join("|"),
+        words = words.join(" "),
+    )
+}
+
+/// Escapes text for use inside a single-quoted `_arguments` spec description.
+fn zsh_escape(text: &str) -> String {
+    let mut escaped: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\'' => escaped.push_str(r"'\''"),
+            '[' | ']' | ':' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn zsh(name: &str, flags: &[&FlagInfo]) -> String {
+    let mut specs: Vec<String> = Vec::new();
+    for flag in flags {
+        let repeatable: bool = matches!(flag.optionality, Optionality::Repeating);
+        let repeat: &str = if repeatable { "*" } else { "" };
+        let description: String = zsh_escape(flag.description);
+        let action: String = match &flag.kind {
+            FlagInfoKind::Switch => String::new(),
+            FlagInfoKind::Option { arg_name } => format!(":{}:_files", arg_name),
+        };
+        // Repeatable flags may follow themselves, so only single-use ones exclude their alias.
+        let spec: String = match flag.short {
+            Some(short) if !repeatable => format!(
+                "'(-{short} {long})'{{-{short},{long}}}'[{description}]{action}'",
+                short = short,
+                long = flag.long,
+            ),
+            Some(short) => format!(
+                "'{repeat}'{{-{short},{long}}}'[{description}]{action}'",
+                short = short,
+                long = flag.long,
+            ),
+            None => format!("'{repeat}{long}[{description}]{action}'", long = flag.long),
+        };
+        specs.push(spec);
+    }
+    specs.push("'*:path:_files'".to_string());
+
+    format!(
+        "#compdef {name}\n\n_{name}() {{\n    _arguments -s \\\n        {specs}\n}}\n\n\
+         _{name} \"$@\"\n",
+        name = name,
+        specs = specs.join(" \\\n        "),
+    )
+}
+
+/// Quotes text as a single fish argument.
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+fn fish(name: &str, flags: &[&FlagInfo]) -> String {
+    let mut script: String = String::new();
+    for flag in flags {
+        let mut line: String = format!("complete -c {}", name);
+        if let Some(short) = flag.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        line.push_str(&format!(" -l {}", long_name(flag)));
+        if takes_value(flag) {
+            line.push_str(" -r");
+        }
+        line.push_str(&format!(" -d {}", fish_quote(flag.description)));
+        script.push_str(&line);
+        script.push('\n');
+    }
+    script
+}
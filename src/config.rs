@@ -0,0 +1,83 @@
+//! Defaults read from the config file, which command line flags override.
+
+use crate::Args;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// The settings `config.toml` may hold; every one is optional.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    retries: Option<u32>,
+    timeout_retries: Option<u32>,
+    connect_timeout: Option<u64>,
+    read_timeout: Option<u64>,
+    stall_timeout: Option<u64>,
+    max_redirects: Option<usize>,
+    output_dir: Option<String>,
+    /// `false` has the same effect as `--no-color`.
+    color: Option<bool>,
+}
+
+/// `$XDG_CONFIG_HOME/rfm/config.toml`, falling back to `~/.config`; `%APPDATA%` on Windows.
+fn default_path() -> Option<PathBuf> {
+    let non_empty = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+    let base: PathBuf = if cfg!(windows) {
+        PathBuf::from(non_empty("APPDATA")?)
+    } else {
+        match non_empty("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(non_empty("HOME")?).join(".config"),
+        }
+    };
+    Some(base.join("rfm").join("config.toml"))
+}
+
+/// Reads the file named by `--config`, or the default one if it exists. A missing default file
+/// just means no config; anything else that goes wrong is returned as a warning to print.
+pub fn load(args: &Args) -> Result<Config, String> {
+    if args.no_config {
+        return Ok(Config::default());
+    }
+    let path: PathBuf = match &args.config {
+        Some(path) => PathBuf::from(path),
+        None => match default_path() {
+            Some(path) if path.is_file() => path,
+            _ => return Ok(Config::default()),
+        },
+    };
+
+    let text: String = std::fs::read_to_string(&path)
+        .map_err(|e| format!("cannot read config file {}: {}", path.display(), e))?;
+    toml::from_str(&text)
+        .map_err(|e| format!("ignoring config file {}: {}", path.display(), e.message()))
+}
+
+impl Config {
+    /// Fills in every setting the command line left unset.
+    pub fn apply(self, args: &mut Args) {
+        args.retries = args.retries.or(self.retries);
+        args.timeout_retries = args.timeout_retries.or(self.timeout_retries);
+        args.connect_timeout = args.connect_timeout.or(self.connect_timeout);
+        args.read_timeout = args.read_timeout.or(self.read_timeout);
+        args.stall_timeout = args.stall_timeout.or(self.stall_timeout);
+
+        // Rejected outside install mode, so only downloads pick it up.
+        if args.install || args.from_file.is_some() {
+            args.max_redirects = args.max_redirects.or(self.max_redirects);
+        }
+
+        // A path that already says where the file goes wins over the default directory.
+        let bare_name = |path: &String| {
+            let path: &Path = Path::new(path);
+            path.is_relative() && path.components().count() == 1 && !path.is_dir()
+        };
+        if args.install && args.output_dir.is_none() && args.path.iter().all(bare_name) {
+            args.output_dir = self.output_dir;
+        }
+
+        if self.color == Some(false) {
+            args.no_color = true;
+        }
+    }
+}
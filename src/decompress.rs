@@ -0,0 +1,103 @@
+//! `--decompress`: unpacking a gzip, bzip2 or xz download on its way to disk, so only the
+//! decompressed file is ever written.
+
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use liblzma::read::XzDecoder;
+use log::debug;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+
+/// The formats `unpack()` tells apart, with the extension a destination loses once its
+/// contents are decompressed.
+pub(crate) const FORMATS: &[(&str, &str)] = &[("gzip", ".gz"), ("bzip2", ".bz2"), ("xz", ".xz")];
+
+/// `dest` without a trailing `.gz`, `.bz2` or `.xz`.
+pub(crate) fn strip_extension(dest: &Path) -> PathBuf {
+    let Some(name) = dest.file_name().and_then(|name| name.to_str()) else {
+        return dest.to_path_buf();
+    };
+    match FORMATS.iter().find_map(|(_, ext)| name.strip_suffix(ext)) {
+        Some(stem) if !stem.is_empty() => dest.with_file_name(stem),
+        _ => dest.to_path_buf(),
+    }
+}
+
+/// The chunks the download reader sends, read back as one stream; `on_chunk` sees each chunk
+/// as the decoder takes it.
+pub(crate) struct ChunkReader<F: FnMut(&[u8])> {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+    on_chunk: F,
+}
+
+impl<F: FnMut(&[u8])> ChunkReader<F> {
+    pub(crate) fn new(receiver: Receiver<Vec<u8>>, on_chunk: F) -> Self {
+        ChunkReader { receiver, chunk: Vec::new(), position: 0, on_chunk }
+    }
+}
+
+impl<F: FnMut(&[u8])> Read for ChunkReader<F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            // A closed channel is the end of the download.
+            let Ok(chunk) = self.receiver.recv() else { return Ok(0) };
+            (self.on_chunk)(&chunk);
+            self.chunk = chunk;
+            self.position = 0;
+        }
+        let n: usize = buf.len().min(self.chunk.len() - self.position);
+        buf[..n].copy_from_slice(&self.chunk[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Decompresses `input` into `output`, telling the format apart by its first bytes, and
+/// returns the number of decompressed bytes. Concatenated streams are decompressed in turn.
+pub(crate) fn unpack(input: impl Read, output: &mut impl Write) -> io::Result<u64> {
+    let mut input: BufReader<_> = BufReader::new(input);
+    let magic: &[u8] = input.fill_buf()?;
+    let mut decoder: Box<dyn Read + '_> = if magic.starts_with(&[0x1f, 0x8b]) {
+        debug!("decompressing gzip data");
+        Box::new(MultiGzDecoder::new(input))
+    } else if magic.starts_with(b"BZh") {
+        debug!("decompressing bzip2 data");
+        Box::new(MultiBzDecoder::new(input))
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        debug!("decompressing xz data");
+        Box::new(XzDecoder::new_multi_decoder(input))
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "--decompress: the download is not gzip, bzip2 or xz data",
+        ));
+    };
+    io::copy(&mut decoder, output)
+}
+
+/// Passes writes through to `inner`, showing `on_write` each part that was written.
+pub(crate) struct Tee<W: Write, F: FnMut(&[u8])> {
+    inner: W,
+    on_write: F,
+}
+
+impl<W: Write, F: FnMut(&[u8])> Tee<W, F> {
+    pub(crate) fn new(inner: W, on_write: F) -> Self {
+        Tee { inner, on_write }
+    }
+}
+
+impl<W: Write, F: FnMut(&[u8])> Write for Tee<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n: usize = self.inner.write(buf)?;
+        (self.on_write)(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,800 @@
+//! Delete mode.
+
+use crate::CHUNK_SIZE;
+use crate::error::RfmError;
+use crate::audit;
+use crate::output::{confirm_each, dry_run_note, paint, Answer};
+use ansi_term::Colour::*;
+use indicatif::HumanBytes;
+use log::debug;
+use std::fs::{
+    read_dir, read_link, remove_dir, remove_dir_all, remove_file, File, Metadata, OpenOptions,
+};
+use std::io::{self, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long `describe_targets()` may spend walking directories before settling for a lower bound.
+const SUMMARY_TIME_LIMIT: Duration = Duration::from_secs(2);
+
+/// Entries `list_tree()` shows per directory before summing up the rest as "... and N more".
+const LIST_ENTRY_LIMIT: usize = 20;
+
+/// Settings for `uninstall()`, resolved from the command line.
+pub struct DeleteOptions {
+    /// Move targets to the system trash rather than removing them.
+    pub trash: bool,
+    /// Report the deletion instead of performing it.
+    pub dry_run: bool,
+    /// Overwrite passes made over a regular file before it is unlinked.
+    pub shred_passes: Option<u32>,
+    /// Delete even the system directories `uninstall()` normally refuses to touch.
+    pub allow_protected: bool,
+    /// Delete what a symlink points to, along with the link, instead of just the link.
+    pub follow_symlinks: bool,
+    /// Levels of a directory deleted, counting its own entries as the first; anything deeper
+    /// is left in place, along with the directories holding it.
+    pub max_depth: Option<usize>,
+    /// Only remove directories, and only those that are empty; anything else is left alone.
+    pub empty_only: bool,
+    /// Ask before removing each entry of a directory, and before going into each directory.
+    pub confirm_each: bool,
+}
+
+impl DeleteOptions {
+    /// Fails on settings that contradict each other, naming the flags that set them.
+    /// `uninstall()` checks this before touching anything.
+    pub fn validate(&self) -> Result<(), RfmError> {
+        let fail = |e: &str| Err(RfmError::InvalidArgs(e.into()));
+        let shred: bool = self.shred_passes.is_some();
+
+        // Shredding destroys the data, so it cannot be combined with keeping it in the trash.
+        if self.shred_passes == Some(0) {
+            return fail("--shred-passes must be at least 1");
+        }
+        if shred && self.trash {
+            return fail("--shred cannot be combined with --trash");
+        }
+
+        // The depth limit is a way of removing directories, which trashing and shredding aren't.
+        if let Some(depth) = self.max_depth {
+            if self.trash || shred {
+                return fail("--max-depth cannot be combined with --trash or --shred");
+            }
+            if depth == 0 {
+                return fail("--max-depth must be at least 1");
+            }
+        }
+
+        // Removing an empty directory has nothing to shred, keep in the trash or leave below a
+        // depth, and asking about every entry replaces removing directories in one go.
+        if self.empty_only && (self.trash || shred || self.max_depth.is_some()) {
+            return fail("--empty-only cannot be combined with --trash, --shred or --max-depth");
+        }
+        if self.confirm_each && (self.trash || shred || self.max_depth.is_some() || self.empty_only)
+        {
+            return fail(
+                "--confirm-each cannot be combined with --trash, --shred, --max-depth or \
+                 --empty-only",
+            );
+        }
+        Ok(())
+    }
+}
+
+/// An answer to `--confirm-each` that holds for the rest of the run, "all" or "quit".
+static LASTING_ANSWER: Mutex<Option<Answer>> = Mutex::new(None);
+
+/// Parses an `--older-than` or `--newer-than` value such as `90s`, `30m`, `24h`, `7d` or `2w`;
+/// a bare number is seconds.
+pub fn parse_age(raw: &str) -> Result<Duration, String> {
+    let (digits, unit): (&str, u64) = match raw.char_indices().last() {
+        Some((i, 's')) => (&raw[..i], 1),
+        Some((i, 'm')) => (&raw[..i], 60),
+        Some((i, 'h')) => (&raw[..i], 60 * 60),
+        Some((i, 'd')) => (&raw[..i], 24 * 60 * 60),
+        Some((i, 'w')) => (&raw[..i], 7 * 24 * 60 * 60),
+        _ => (raw, 1),
+    };
+    match digits.parse::<u64>().ok().and_then(|n| n.checked_mul(unit)) {
+        Some(secs) => Ok(Duration::from_secs(secs)),
+        None => Err(format!("invalid age {:?}, expected a number and a unit like 24h or 7d", raw)),
+    }
+}
+
+/// Whether `path` was last modified longer ago than `older_than` and more recently than
+/// `newer_than`, as far as each is given. `follow_symlinks` judges a link by its target.
+pub fn modified_within(
+    path: &Path,
+    older_than: Option<Duration>,
+    newer_than: Option<Duration>,
+    follow_symlinks: bool,
+) -> io::Result<bool> {
+    let meta: Metadata = if follow_symlinks { path.metadata()? } else { path.symlink_metadata()? };
+    // A time in the future, from clock skew or a copied archive, counts as just now.
+    let age: Duration = SystemTime::now().duration_since(meta.modified()?).unwrap_or_default();
+    Ok(older_than.is_none_or(|min| age > min) && newer_than.is_none_or(|max| age < max))
+}
+
+/// Parses a `--larger-than` or `--smaller-than` value such as `500k`, `10M` or `1G`, in
+/// powers of 1024; a bare number is bytes.
+pub fn parse_size(raw: &str) -> Result<u64, String> {
+    let (digits, multiplier): (&str, u64) = match raw.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&raw[..i], 1024),
+        Some((i, 'm' | 'M')) => (&raw[..i], 1024 * 1024),
+        Some((i, 'g' | 'G')) => (&raw[..i], 1024 * 1024 * 1024),
+        Some((i, 't' | 'T')) => (&raw[..i], 1024 * 1024 * 1024 * 1024),
+        _ => (raw, 1),
+    };
+    match digits.parse::<u64>().ok().and_then(|n| n.checked_mul(multiplier)) {
+        Some(size) => Ok(size),
+        None => Err(format!("invalid size {:?}, expected a number of bytes like 10M or 1G", raw)),
+    }
+}
+
+/// The bytes of every file beneath `dir`, without following symlinks. Entries that can't be
+/// read are skipped.
+fn tree_bytes(dir: &Path) -> u64 {
+    let Ok(entries) = read_dir(dir) else { return 0 };
+    let mut bytes: u64 = 0;
+    for entry in entries.flatten() {
+        let Ok(meta) = entry.path().symlink_metadata() else { continue };
+        if meta.is_dir() {
+            bytes += tree_bytes(&entry.path());
+        } else if meta.is_file() {
+            bytes += meta.len();
+        }
+    }
+    bytes
+}
+
+/// Whether `path` is larger than `larger_than` and smaller than `smaller_than` bytes, as far as
+/// each is given. A directory counts as the files it holds, however deep.
+pub fn size_within(
+    path: &Path,
+    larger_than: Option<u64>,
+    smaller_than: Option<u64>,
+    follow_symlinks: bool,
+) -> io::Result<bool> {
+    let meta: Metadata = if follow_symlinks { path.metadata()? } else { path.symlink_metadata()? };
+    let size: u64 = if meta.is_dir() { tree_bytes(path) } else { meta.len() };
+    Ok(larger_than.is_none_or(|min| size > min) && smaller_than.is_none_or(|max| size < max))
+}
+
+/// Directories whose loss would break the system or wipe a user's files wholesale.
+#[cfg(unix)]
+const PROTECTED_PATHS: &[&str] = &[
+    "/", "/bin", "/boot", "/dev", "/etc", "/home", "/lib", "/lib64", "/opt", "/proc", "/root",
+    "/sbin", "/srv", "/sys", "/usr", "/usr/bin", "/usr/lib", "/usr/local", "/usr/sbin", "/var",
+    "/Applications", "/Library", "/System", "/Users",
+];
+
+#[cfg(windows)]
+const PROTECTED_PATHS: &[&str] = &[r"C:\", r"C:\Windows", r"C:\Program Files", r"C:\Users"];
+
+#[cfg(not(any(unix, windows)))]
+const PROTECTED_PATHS: &[&str] = &[];
+
+/// Canonical forms of the protected paths, plus the home directory itself.
+fn protected_paths() -> Vec<PathBuf> {
+    let home: Option<PathBuf> = std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from);
+
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for path in PROTECTED_PATHS.iter().map(PathBuf::from).chain(home) {
+        // On merged-/usr systems `/bin` resolves to `/usr/bin`; keep both spellings.
+        if let Ok(canonical) = path.canonicalize() {
+            paths.push(canonical);
+        }
+        paths.push(path);
+    }
+    paths
+}
+
+/// Where deleting `path` would actually take effect: `..` and symlinked parents are resolved,
+/// but a symlink itself is left alone since deleting it only removes the link.
+fn effective_path(path: &Path) -> io::Result<PathBuf> {
+    if !path.symlink_metadata()?.is_symlink() {
+        return path.canonicalize();
+    }
+    let parent: &Path = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Ok(parent.canonicalize()?.join(path.file_name().unwrap_or_default()))
+}
+
+/// What walking a directory found before it finished or ran out of time.
+struct TreeSummary {
+    entries: u64,
+    bytes: u64,
+    complete: bool,
+}
+
+/// Counts the entries and file bytes beneath `dir` without following symlinks, stopping at
+/// `deadline`. Entries that can't be read are skipped.
+fn summarize_tree(dir: &Path, deadline: Instant, summary: &mut TreeSummary) {
+    let Ok(entries) = read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        if Instant::now() >= deadline {
+            summary.complete = false;
+            return;
+        }
+        summary.entries += 1;
+        let Ok(meta) = entry.path().symlink_metadata() else { continue };
+        if meta.is_dir() {
+            summarize_tree(&entry.path(), deadline, summary);
+            if !summary.complete {
+                return;
+            }
+        } else if meta.is_file() {
+            summary.bytes += meta.len();
+        }
+    }
+}
+
+/// A short description of `path` such as "file, 1.20 MiB", for the delete confirmation.
+/// Symlinks say where they point and whether that is deleted too.
+fn describe(path: &Path, deadline: Instant, follow_symlinks: bool) -> String {
+    let meta: Metadata = match path.symlink_metadata() {
+        Ok(meta) => meta,
+        Err(_) => return "not found".into(),
+    };
+    if meta.is_symlink() {
+        let link: String = match read_link(path) {
+            Ok(target) => format!("symlink to {}", target.display()),
+            Err(_) => "symlink".into(),
+        };
+        return match path.canonicalize() {
+            Ok(target) if follow_symlinks => format!(
+                "{}, which is deleted too: {}",
+                link,
+                describe(&target, deadline, false)
+            ),
+            Err(_) if follow_symlinks => format!("{}, which does not exist", link),
+            _ => format!("{}; only the link is removed", link),
+        };
+    }
+    if meta.is_file() {
+        return format!("file, {}", HumanBytes(meta.len()));
+    }
+    if !meta.is_dir() {
+        return "special file".into();
+    }
+
+    let mut summary: TreeSummary = TreeSummary { entries: 0, bytes: 0, complete: true };
+    summarize_tree(path, deadline, &mut summary);
+    let noun: &str = if summary.entries == 1 { "entry" } else { "entries" };
+    if summary.complete {
+        format!("directory, {} {}, {}", summary.entries, noun, HumanBytes(summary.bytes))
+    } else {
+        format!(
+            "directory, at least {} {} and {}, stopped counting after {}s",
+            summary.entries,
+            noun,
+            HumanBytes(summary.bytes),
+            SUMMARY_TIME_LIMIT.as_secs()
+        )
+    }
+}
+
+/// Describes each of `paths` for the delete confirmation: its type, and its size, summed over
+/// the whole tree for directories. Large trees are only counted for `SUMMARY_TIME_LIMIT` in total.
+pub fn describe_targets(paths: &[String], follow_symlinks: bool) -> Vec<String> {
+    let deadline: Instant = Instant::now() + SUMMARY_TIME_LIMIT;
+    paths.iter().map(|path| describe(Path::new(path), deadline, follow_symlinks)).collect()
+}
+
+/// Lists what is inside the directory `dir` for `--list`, one indented line per entry with
+/// file sizes, going `max_depth` levels down. Deeper directories only say how many entries they
+/// hold, and each directory shows at most `LIST_ENTRY_LIMIT` entries. Symlinks aren't followed.
+pub fn list_tree(dir: &Path, max_depth: usize) -> Vec<String> {
+    let mut lines: Vec<String> = vec![paint(Blue, format!("{}/", dir.display())).to_string()];
+    list_entries(dir, 1, max_depth, &mut lines);
+    lines
+}
+
+fn list_entries(dir: &Path, depth: usize, max_depth: usize, lines: &mut Vec<String>) {
+    let indent: String = "  ".repeat(depth);
+    let mut entries: Vec<PathBuf> = match read_dir(dir) {
+        Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+        Err(e) => {
+            lines.push(format!("{}{}", indent, paint(Red, format!("cannot be read: {}", e))));
+            return;
+        }
+    };
+    entries.sort();
+
+    for path in entries.iter().take(LIST_ENTRY_LIMIT) {
+        let name: String = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let Ok(meta) = path.symlink_metadata() else { continue };
+        if meta.is_symlink() {
+            let target: String =
+                read_link(path).map_or("?".into(), |target| target.display().to_string());
+            lines.push(format!("{}{} -> {}", indent, paint(Cyan, name), target));
+        } else if meta.is_dir() {
+            let name: ansi_term::ANSIString = paint(Blue, format!("{}/", name));
+            if depth < max_depth {
+                lines.push(format!("{}{}", indent, name));
+                list_entries(path, depth + 1, max_depth, lines);
+            } else {
+                let count: usize = read_dir(path).map_or(0, |entries| entries.count());
+                let noun: &str = if count == 1 { "entry" } else { "entries" };
+                lines.push(format!("{}{} ({} {})", indent, name, count, noun));
+            }
+        } else {
+            lines.push(format!("{}{}  {}", indent, name, HumanBytes(meta.len())));
+        }
+    }
+    if entries.len() > LIST_ENTRY_LIMIT {
+        lines.push(format!("{}... and {} more", indent, entries.len() - LIST_ENTRY_LIMIT));
+    }
+}
+
+/// Whether this build has a system trash to move into, for `--trash`.
+pub(crate) const TRASH_SUPPORTED: bool =
+    cfg!(any(windows, all(unix, not(any(target_os = "ios", target_os = "android")))));
+
+#[cfg(any(windows, all(unix, not(any(target_os = "ios", target_os = "android")))))]
+fn move_to_trash(path: &Path) -> io::Result<()> {
+    trash::delete(path).map_err(|e| {
+        io::Error::other(format!("could not move {} to the trash: {}", path.display(), e))
+    })
+}
+
+/// Never fall back to permanent deletion where there is no trash to move into.
+#[cfg(not(any(windows, all(unix, not(any(target_os = "ios", target_os = "android"))))))]
+fn move_to_trash(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--trash is not supported on this platform, nothing was deleted",
+    ))
+}
+
+/// Overwrites every byte of the file at `path` with random data `passes` times, syncing
+/// each pass to disk, and returns the number of bytes written.
+fn shred_file(path: &Path, passes: u32) -> io::Result<u64> {
+    let mut file: File = OpenOptions::new().write(true).open(path)?;
+    let len: u64 = file.metadata()?.len();
+    let mut buffer: [u8; CHUNK_SIZE] = [0; CHUNK_SIZE];
+    let mut written: u64 = 0;
+
+    for pass in 1..=passes {
+        debug!("shred pass {}/{} over {} bytes of {}", pass, passes, len, path.display());
+        file.rewind()?;
+        let mut remaining: u64 = len;
+        while remaining > 0 {
+            let n: usize = remaining.min(CHUNK_SIZE as u64) as usize;
+            getrandom::getrandom(&mut buffer[..n])?;
+            file.write_all(&buffer[..n])?;
+            remaining -= n as u64;
+        }
+        // Each pass has to reach the disk, or the next one may simply replace it in the page cache.
+        file.sync_all()?;
+        written += len;
+    }
+
+    Ok(written)
+}
+
+/// What `remove_within()` removed, or would remove under `--dry-run`.
+struct DepthReport {
+    removed: u64,
+    /// Directories at the depth limit that still hold entries, so were kept.
+    left: Vec<PathBuf>,
+}
+
+/// Removes the entries of `dir`, which sit at `depth`, and those of its subdirectories down to
+/// `max_depth`, then `dir` itself once nothing is left in it. A directory at the limit is only
+/// removed if it's empty. Symlinks are removed as links. Returns whether `dir` was removed.
+fn remove_within(
+    dir: &Path,
+    depth: usize,
+    max_depth: usize,
+    dry_run: bool,
+    report: &mut DepthReport,
+) -> io::Result<bool> {
+    let mut emptied: bool = true;
+    for entry in read_dir(dir)? {
+        let path: PathBuf = entry?.path();
+        if !path.symlink_metadata()?.is_dir() {
+            if !dry_run {
+                remove_link(&path)?;
+            }
+            report.removed += 1;
+        } else if depth < max_depth {
+            emptied &= remove_within(&path, depth + 1, max_depth, dry_run, report)?;
+        } else if read_dir(&path)?.next().is_none() {
+            if !dry_run {
+                remove_dir(&path)?;
+            }
+            report.removed += 1;
+        } else {
+            report.left.push(path);
+            emptied = false;
+        }
+    }
+    if emptied {
+        if !dry_run {
+            remove_dir(dir)?;
+        }
+        report.removed += 1;
+    }
+    Ok(emptied)
+}
+
+/// Deletes the directory `path` no deeper than `max_depth` and reports what was kept.
+fn remove_to_depth(path: &Path, max_depth: usize, dry_run: bool) -> io::Result<()> {
+    let mut report: DepthReport = DepthReport { removed: 0, left: Vec::new() };
+    let removed_all: bool = remove_within(path, 1, max_depth, dry_run, &mut report)?;
+    // The directory itself was counted with its entries once it went.
+    let entries: u64 = report.removed - u64::from(removed_all);
+    let noun: &str = if entries == 1 { "entry" } else { "entries" };
+
+    if dry_run {
+        dry_run_note(&format!("Would remove {} {} from {:#?}", entries, noun, path));
+    } else {
+        say!("{}: Removed {} {} from {:#?}", paint(Green, "Success"), entries, noun, path);
+    }
+    if removed_all {
+        if dry_run {
+            dry_run_note(&format!("Would remove directory {:#?}", path));
+        } else {
+            say!("{}: Uninstalled path: {:#?}", paint(Green, "Success"), path);
+        }
+        return Ok(());
+    }
+    notice!(
+        "{} kept {} {} with entries below --max-depth {}:",
+        paint(Yellow, "Note:"),
+        report.left.len(),
+        if report.left.len() == 1 { "directory" } else { "directories" },
+        max_depth
+    );
+    for dir in &report.left {
+        notice!("  {}", paint(Yellow, dir.display().to_string()));
+    }
+    Ok(())
+}
+
+/// What `remove_each()` removed, or would remove under `--dry-run`, and what it was told to keep.
+struct EachReport {
+    removed: u64,
+    kept: u64,
+}
+
+/// Asks `question` for `--confirm-each` unless "all" or "quit" already answered it.
+fn ask_each(question: &str) -> io::Result<bool> {
+    let mut lasting = LASTING_ANSWER.lock().unwrap();
+    let answer: Answer = match *lasting {
+        Some(answer) => answer,
+        None => confirm_each(question)?,
+    };
+    if matches!(answer, Answer::All | Answer::Quit) {
+        *lasting = Some(answer);
+    }
+    if answer == Answer::Quit {
+        audit::mark_declined();
+    }
+    Ok(matches!(answer, Answer::Yes | Answer::All))
+}
+
+/// Removes `path` for `--confirm-each`, asking before each entry and before going into each
+/// directory. A directory goes once everything in it did and it was confirmed too. Symlinks
+/// are removed as links. Returns whether `path` was removed.
+fn remove_each(path: &Path, dry_run: bool, report: &mut EachReport) -> io::Result<bool> {
+    let meta: Metadata = path.symlink_metadata()?;
+    if !meta.is_dir() {
+        let kind: &str = if meta.is_symlink() { "symlink" } else { "file" };
+        if !ask_each(&format!("Remove {} {:#?}", kind, path))? {
+            report.kept += 1;
+            return Ok(false);
+        }
+        if dry_run {
+            dry_run_note(&format!("Would remove {} {:#?}", kind, path));
+        } else {
+            remove_link(path)?;
+        }
+        report.removed += 1;
+        return Ok(true);
+    }
+
+    if !ask_each(&format!("Go into directory {:#?}", path))? {
+        report.kept += 1;
+        return Ok(false);
+    }
+    let mut entries: Vec<PathBuf> =
+        read_dir(path)?.map(|entry| entry.map(|entry| entry.path())).collect::<io::Result<_>>()?;
+    entries.sort();
+    let mut emptied: bool = true;
+    for entry in &entries {
+        emptied &= remove_each(entry, dry_run, report)?;
+    }
+    // What was kept inside keeps the directory too, so there is nothing to ask.
+    if !emptied || !ask_each(&format!("Remove directory {:#?}", path))? {
+        report.kept += 1;
+        return Ok(false);
+    }
+    if dry_run {
+        dry_run_note(&format!("Would remove directory {:#?}", path));
+    } else {
+        remove_dir(path)?;
+    }
+    report.removed += 1;
+    Ok(true)
+}
+
+/// Deletes `path` one confirmed entry at a time and reports what was kept.
+fn remove_confirmed(path: &Path, link: Option<&Path>, dry_run: bool) -> io::Result<()> {
+    // Quitting answers no to whatever targets were still to come.
+    if *LASTING_ANSWER.lock().unwrap() == Some(Answer::Quit) {
+        debug!("not asking about {} after quit", path.display());
+        return Ok(());
+    }
+    let mut report: EachReport = EachReport { removed: 0, kept: 0 };
+    let removed_all: bool = remove_each(path, dry_run, &mut report)?;
+    // Once its target is gone, a followed link would only be left dangling.
+    if removed_all && let Some(link) = link {
+        if dry_run {
+            dry_run_note(&format!("Would remove symlink {:#?}", link));
+        } else {
+            remove_link(link)?;
+        }
+    }
+    let noun = |count: u64| if count == 1 { "entry" } else { "entries" };
+    if report.removed == 0 {
+        notice!("{} kept {:#?}", paint(Yellow, "Note:"), path);
+    } else if removed_all {
+        // Under --dry-run each entry was already noted as it was answered.
+        if !dry_run {
+            say!("{}: Uninstalled path: {:#?}", paint(Green, "Success"), path);
+        }
+    } else if dry_run {
+        dry_run_note(&format!(
+            "Would remove {} {} of {:#?}, keeping {}",
+            report.removed,
+            noun(report.removed),
+            path,
+            report.kept
+        ));
+    } else {
+        say!(
+            "{}: Removed {} {} of {:#?}, kept {}",
+            paint(Green, "Success"),
+            report.removed,
+            noun(report.removed),
+            path,
+            report.kept
+        );
+    }
+    Ok(())
+}
+
+/// Removes the directory `path` for `--empty-only` if nothing is in it, skipping it with a note
+/// otherwise, along with the symlink `link` that led to it.
+fn remove_if_empty(path: &Path, link: Option<&Path>, dry_run: bool) -> Result<(), RfmError> {
+    if !path.symlink_metadata()?.is_dir() {
+        return Err(RfmError::InvalidArgs(format!(
+            "{} is not a directory, and --empty-only only removes directories",
+            path.display()
+        )));
+    }
+    let skip = || notice!("{} skipped {:#?}, which is not empty", paint(Yellow, "Note:"), path);
+
+    if dry_run {
+        if read_dir(path)?.next().is_some() {
+            skip();
+            return Ok(());
+        }
+        dry_run_note(&format!("Would remove empty directory {:#?}", path));
+        if let Some(link) = link {
+            dry_run_note(&format!("Would remove symlink {:#?}", link));
+        }
+        return Ok(());
+    }
+
+    // Non-recursive, so whatever lands in the directory in the meantime stops it too.
+    debug!("removing {} if it is empty", path.display());
+    match remove_dir(path) {
+        Err(e) if e.kind() == io::ErrorKind::DirectoryNotEmpty => {
+            skip();
+            return Ok(());
+        }
+        removed => removed?,
+    }
+    if let Some(link) = link {
+        remove_link(link)?;
+    }
+    say!("{}: Removed empty directory: {:#?}", paint(Green, "Success"), path);
+    Ok(())
+}
+
+/// Removes the symlink at `path`; Windows keeps directory links as directories.
+fn remove_link(path: &Path) -> io::Result<()> {
+    remove_file(path).or_else(|e| if cfg!(windows) { std::fs::remove_dir(path) } else { Err(e) })
+}
+
+/// Deletes `path`, recursively for directories, or moves it to the trash. A symlink is removed
+/// itself, unless `follow_symlinks` asks for what it points to to go as well.
+pub fn uninstall(path: &str, opts: &DeleteOptions) -> Result<(), RfmError> {
+    opts.validate()?;
+    let (trash, dry_run): (bool, bool) = (opts.trash, opts.dry_run);
+    // Convert user input into a Path for filesystem checks and operations.
+    let link: &Path = Path::new(path);
+
+    // Fail fast with a colorized message when the target does not exist. A dangling symlink
+    // still exists as far as deleting it goes.
+    let is_link: bool = match link.symlink_metadata() {
+        Ok(meta) => meta.is_symlink(),
+        Err(_) => false,
+    };
+    if !link.exists() && (!is_link || opts.follow_symlinks) {
+        return Err(RfmError::NotFound(link.to_path_buf()));
+    }
+
+    // From here on `path` is what gets deleted: the link itself, or where it points.
+    let followed: bool = is_link && opts.follow_symlinks;
+    let resolved: PathBuf;
+    let path: &Path = if followed {
+        resolved = link.canonicalize()?;
+        debug!("{} is a symlink to {}, deleting through it", link.display(), resolved.display());
+        &resolved
+    } else {
+        link
+    };
+
+    // A slip of the keyboard shouldn't be able to take the whole system with it.
+    if !opts.allow_protected {
+        let target: PathBuf = effective_path(path)?;
+        if protected_paths().contains(&target) {
+            return Err(RfmError::InvalidArgs(format!(
+                "refusing to delete protected system path {}, pass --i-know-what-im-doing to \
+                 delete it anyway",
+                target.display()
+            )));
+        }
+    }
+
+    // Shredding a directory or a link's target isn't what deleting that path means, so refuse.
+    if opts.shred_passes.is_some() && !path.symlink_metadata()?.is_file() {
+        return Err(RfmError::InvalidArgs(format!(
+            "{} is not a regular file and cannot be shredded",
+            path.display()
+        )));
+    }
+
+    // Pruning empty directories should never cost anything that had contents.
+    if opts.empty_only {
+        return remove_if_empty(path, followed.then_some(link), dry_run);
+    }
+
+    if opts.confirm_each {
+        return Ok(remove_confirmed(path, followed.then_some(link), dry_run)?);
+    }
+
+    // Only a directory has anything below the depth limit; files and links go as usual.
+    let depth_limited: Option<usize> =
+        opts.max_depth.filter(|_| (!is_link || followed) && path.is_dir());
+
+    if dry_run {
+        if let Some(max_depth) = depth_limited {
+            remove_to_depth(path, max_depth, true)?;
+            if followed {
+                dry_run_note(&format!("Would remove symlink {:#?}", link));
+            }
+            return Ok(());
+        }
+        let kind: &str = if is_link && !followed {
+            "symlink"
+        } else if path.is_file() {
+            "file"
+        } else {
+            "directory"
+        };
+        let action: &str = match (trash, opts.shred_passes) {
+            (true, _) => "move to trash",
+            (false, Some(_)) => "shred and remove",
+            (false, None) => "remove",
+        };
+        dry_run_note(&format!("Would {} {} {:#?}", action, kind, path));
+        if followed {
+            dry_run_note(&format!("Would remove symlink {:#?}", link));
+        }
+        return Ok(());
+    }
+
+    // Trash keeps the target recoverable; otherwise remove files directly and directories recursively.
+    debug!(
+        "deleting {} ({})",
+        path.display(),
+        if trash {
+            "to trash"
+        } else if is_link && !followed {
+            "symlink"
+        } else if path.is_file() {
+            "file"
+        } else {
+            "directory, recursively"
+        }
+    );
+    // Once its target is gone, a followed link would only be left dangling.
+    let remove_followed_link = || if followed { remove_link(link) } else { Ok(()) };
+
+    if trash {
+        move_to_trash(path)?;
+        remove_followed_link()?;
+        say!("{}: Moved to trash: {:#?}", paint(Green, "Success"), path);
+        return Ok(());
+    }
+
+    if let Some(passes) = opts.shred_passes {
+        let overwritten: u64 = shred_file(path, passes)?;
+        remove_file(path)?;
+        remove_followed_link()?;
+        say!(
+            "{}: Shredded path: {:#?} ({} bytes overwritten)",
+            paint(Green, "Success"),
+            path,
+            overwritten
+        );
+        return Ok(());
+    }
+
+    if let Some(max_depth) = depth_limited {
+        remove_to_depth(path, max_depth, false)?;
+        // The link still leads somewhere unless the whole target went.
+        if !path.exists() {
+            remove_followed_link()?;
+        }
+        return Ok(());
+    }
+
+    if is_link && !followed {
+        remove_link(path)?;
+    } else if path.is_file() {
+        remove_file(path)?;
+    } else {
+        remove_dir_all(path)?;
+    }
+    remove_followed_link()?;
+
+    // Report successful deletion with the resolved path.
+    say!("{}: Uninstalled path: {:#?}", paint(Green, "Success"), path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> DeleteOptions {
+        DeleteOptions {
+            trash: false,
+            dry_run: false,
+            shred_passes: None,
+            allow_protected: false,
+            follow_symlinks: false,
+            max_depth: None,
+            empty_only: false,
+            confirm_each: false,
+        }
+    }
+
+    #[test]
+    fn contradicting_ways_of_deleting_are_refused() {
+        assert!(options().validate().is_ok());
+        let refused: [DeleteOptions; 4] = [
+            DeleteOptions { trash: true, shred_passes: Some(1), ..options() },
+            DeleteOptions { max_depth: Some(0), ..options() },
+            DeleteOptions { empty_only: true, max_depth: Some(2), ..options() },
+            DeleteOptions { confirm_each: true, trash: true, ..options() },
+        ];
+        for opts in refused {
+            assert!(matches!(opts.validate(), Err(RfmError::InvalidArgs(_))));
+            // Nothing is looked at before the options are.
+            assert!(matches!(uninstall("/nonexistent", &opts), Err(RfmError::InvalidArgs(_))));
+        }
+    }
+}
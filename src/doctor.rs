@@ -0,0 +1,179 @@
+//! `--doctor`: checks of what a download depends on, each reported as passed or failed, so a
+//! "download doesn't work" report can start from what this machine actually sees.
+
+use crate::error::RfmError;
+use crate::install::{
+    build_client, parse_proxy, probe_writable, redact_proxy, InstallOptions, PROXY_VARS,
+};
+use crate::{archive, decompress, delete};
+use crate::output::paint;
+use ansi_term::Colour::*;
+use log::debug;
+use reqwest::Url;
+use reqwest::blocking::{Client, Response};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Asked for when `--url` doesn't name another one; reserved for exactly this kind of use.
+const DEFAULT_URL: &str = "https://example.com/";
+
+/// Prints a line for one check, and whether it passed.
+fn report(name: &str, outcome: Result<String, String>) -> bool {
+    match outcome {
+        Ok(detail) => {
+            say!("{} {}: {}", paint(Green, "[pass]"), name, detail);
+            true
+        }
+        Err(detail) => {
+            say!("{} {}: {}", paint(Red, "[FAIL]"), name, detail);
+            false
+        }
+    }
+}
+
+/// What this build can do beyond plain downloads, read off the same tables and `cfg`s the
+/// code itself goes by.
+fn features() -> Vec<&'static str> {
+    let mut features: Vec<&'static str> = Vec::new();
+    features.extend(decompress::FORMATS.iter().map(|(format, _)| *format));
+    features.extend(archive::FORMATS);
+    if delete::TRASH_SUPPORTED {
+        features.push("trash");
+    }
+    features
+}
+
+/// The proxy requests go through, with credentials masked, and where it was configured.
+fn describe_proxy(opts: &InstallOptions) -> Result<String, String> {
+    if let Some(raw) = opts.proxy {
+        return parse_proxy(raw).map(|url| format!("{} (from --proxy)", redact_proxy(&url)));
+    }
+    for name in PROXY_VARS {
+        if let Ok(raw) = std::env::var(name)
+            && !raw.is_empty()
+        {
+            let url: Url = parse_proxy(&raw).map_err(|e| format!("{}: {}", name, e))?;
+            let no_proxy = std::env::var("NO_PROXY").or_else(|_| std::env::var("no_proxy"));
+            let bypass: String = match no_proxy {
+                Ok(hosts) if !hosts.is_empty() => format!(", bypassed for {}", hosts),
+                _ => String::new(),
+            };
+            return Ok(format!("{} (from {}{})", redact_proxy(&url), name, bypass));
+        }
+    }
+    Ok("none, connecting directly".into())
+}
+
+/// Which certificates the server has to present.
+fn describe_tls(opts: &InstallOptions) -> String {
+    let roots: String = match opts.cacert {
+        Some(path) => format!("system roots plus {}", path.display()),
+        None => "system roots".into(),
+    };
+    if opts.insecure {
+        format!("{}, but certificate checks are OFF (--insecure)", roots)
+    } else {
+        format!("{}, certificates checked", roots)
+    }
+}
+
+/// Whether files can be created in `dir`, tested the way a download tests its destination.
+fn check_writable(dir: &Path) -> Result<String, String> {
+    match probe_writable(dir) {
+        Ok(()) => Ok(format!("{} is writable", dir.display())),
+        Err(e) => Err(format!("cannot write to {}: {}", dir.display(), e)),
+    }
+}
+
+/// Looks up the host of `url`, which tells a DNS problem apart from one further along. Behind
+/// a proxy the proxy does the lookup, so a local failure doesn't count.
+fn check_dns(url: &Url, proxied: bool) -> Result<String, String> {
+    let host: &str = url.host_str().ok_or_else(|| format!("{} has no host", url))?;
+    let port: u16 = url.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<SocketAddr> = match (host, port).to_socket_addrs() {
+        Ok(addrs) => addrs.collect(),
+        Err(e) if proxied => {
+            return Ok(format!("cannot resolve {} here ({}), left to the proxy", host, e));
+        }
+        Err(e) => return Err(format!("cannot resolve {}: {}", host, e)),
+    };
+    let shown: Vec<String> = addrs.iter().take(3).map(|addr| addr.ip().to_string()).collect();
+    Ok(format!("{} resolves to {}", host, shown.join(", ")))
+}
+
+/// `e` followed by every error beneath it, which is where the actual cause usually is.
+fn error_chain(e: &dyn std::error::Error) -> String {
+    let mut text: String = e.to_string();
+    let mut source: Option<&dyn std::error::Error> = e.source();
+    while let Some(cause) = source {
+        // Some wrappers repeat their cause's message word for word.
+        let cause_text: String = cause.to_string();
+        if !text.ends_with(&cause_text) {
+            text.push_str(&format!(": {}", cause_text));
+        }
+        source = cause.source();
+    }
+    text
+}
+
+/// Sends a HEAD for `url` through `client`, as a download would.
+fn check_request(client: &Client, url: &Url, opts: &InstallOptions) -> Result<String, String> {
+    let started: Instant = Instant::now();
+    debug!("HEAD {}", url);
+    let response: Response = client
+        .head(url.as_str())
+        .headers(opts.headers.clone())
+        .send()
+        .map_err(|e| format!("HEAD {} failed: {}", url, error_chain(&e)))?;
+    let elapsed: Duration = started.elapsed();
+    let status = response.status();
+    let detail: String = format!("HEAD {} answered {} in {}ms", url, status, elapsed.as_millis());
+    // A refused HEAD still proves the connection and TLS work.
+    if status.is_server_error() { Err(detail) } else { Ok(detail) }
+}
+
+/// Runs every check against `url`, or a well-known site without one, printing pass or fail for
+/// each. Fails with the number of checks that didn't pass.
+pub fn diagnose(url: Option<&str>, opts: &InstallOptions) -> Result<u64, RfmError> {
+    say!(
+        "rfm {} on {} {}, features: {}",
+        crate::VERSION,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        features().join(", ")
+    );
+
+    let mut outcomes: Vec<bool> = Vec::new();
+    outcomes.push(report("proxy", describe_proxy(opts)));
+    outcomes.push(report("TLS", Ok(describe_tls(opts))));
+    outcomes.push(report("temp directory", check_writable(&std::env::temp_dir())));
+    let destination: &Path = opts.output_dir.unwrap_or(Path::new("."));
+    outcomes.push(report("destination", check_writable(destination)));
+
+    let url: Url = Url::parse(url.unwrap_or(DEFAULT_URL))
+        .map_err(|e| RfmError::InvalidArgs(format!("invalid --url: {}", e)))?;
+    let proxied: bool = opts.proxy.is_some()
+        || PROXY_VARS.iter().any(|name| std::env::var(name).is_ok_and(|raw| !raw.is_empty()));
+    outcomes.push(report("DNS", check_dns(&url, proxied)));
+    match build_client(opts) {
+        Ok(client) => {
+            let accepted: String = "proxy, certificates and timeouts accepted".into();
+            outcomes.push(report("client", Ok(accepted)));
+            outcomes.push(report("connection", check_request(&client, &url, opts)));
+        }
+        Err(e) => outcomes.push(report("client", Err(e.to_string()))),
+    }
+
+    let failed: usize = outcomes.iter().filter(|passed| !**passed).count();
+    say!(
+        "{}: {} passed, {} failed",
+        if failed == 0 { paint(Green, "Summary") } else { paint(Red, "Summary") },
+        outcomes.len() - failed,
+        failed
+    );
+    if failed > 0 {
+        return Err(RfmError::Incomplete { failed, total: outcomes.len(), what: "checks" });
+    }
+    Ok(0)
+}
@@ -0,0 +1,120 @@
+//! The error every operation returns, so callers can tell failures apart.
+
+use reqwest::StatusCode;
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RfmError {
+    /// A path the operation reads from does not exist.
+    #[error("{} does not exist", .0.display())]
+    NotFound(PathBuf),
+
+    /// The server could not be reached, or the connection broke.
+    #[error(transparent)]
+    Network(#[from] reqwest::Error),
+
+    /// The server took longer than the configured limit, or the run outlasted `--deadline`.
+    #[error("timed out after {secs}s {waiting_for}")]
+    Timeout { secs: u64, waiting_for: &'static str },
+
+    /// The server answered with a status whose body is not the requested file.
+    #[error("Server returned {status} for {url}")]
+    HttpStatus {
+        status: StatusCode,
+        /// URL that answered, after following redirects.
+        url: String,
+    },
+
+    /// The server kept redirecting past the `--max-redirects` limit.
+    #[error("more than {limit} redirects: {}", .chain.join(" -> "))]
+    TooManyRedirects {
+        limit: usize,
+        /// Every URL requested, starting with the original one.
+        chain: Vec<String>,
+    },
+
+    /// `--strict` refused a redirect from HTTPS to plain HTTP.
+    #[error("refusing redirect from HTTPS to plain HTTP {url} under --strict")]
+    InsecureRedirect {
+        /// The plain HTTP URL redirected to.
+        url: String,
+    },
+
+    /// The downloaded bytes don't hash to the digest passed with `--sha256`, `--sha1` or `--md5`.
+    #[error("{algorithm} mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { algorithm: &'static str, expected: String, actual: String },
+
+    /// The checksum file from `--checksum-url` or `--checksum-sidecar` has no usable digest.
+    #[error("cannot use checksum file {url}: {reason}")]
+    ChecksumFile { url: String, reason: String },
+
+    /// `--github` found no release to install from, or not exactly one asset in it.
+    #[error("cannot install from a release of {repo}: {reason}")]
+    Release { repo: String, reason: String },
+
+    /// The download's length differs from the one passed with `--expected-size`.
+    #[error("size mismatch: expected {expected} bytes, got {actual}")]
+    SizeMismatch { expected: u64, actual: u64 },
+
+    /// The destination exists and overwriting it wasn't allowed.
+    #[error("{} already exists, pass --force to overwrite it", .0.display())]
+    DestinationExists(PathBuf),
+
+    /// The arguments don't describe an operation that can run.
+    #[error("{0}")]
+    InvalidArgs(String),
+
+    /// A move across filesystems copied the source over but couldn't remove the original, so
+    /// the copy was removed again unless `kept_copy`; either way the original is still there.
+    #[error(
+        "copied {} to {} but could not remove the original: {source}; {}",
+        .from.display(),
+        .to.display(),
+        if *.kept_copy {
+            "both are left in place"
+        } else {
+            "the copy was removed again, leaving the original as it was"
+        }
+    )]
+    SourceNotRemoved { from: PathBuf, to: PathBuf, kept_copy: bool, source: io::Error },
+
+    /// Some entries of a batch failed; each was reported as it happened.
+    #[error("{failed} of {total} {what} failed")]
+    Incomplete { failed: usize, total: usize, what: &'static str },
+
+    /// Any other filesystem or stream failure.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl RfmError {
+    /// The process exit code for this failure. These are stable, so scripts can branch on them:
+    ///
+    /// | code | meaning |
+    /// |------|---------|
+    /// | 1 | any other I/O failure, or some entries of a batch failed |
+    /// | 2 | invalid arguments, or no single `--github` release asset to install |
+    /// | 3 | network error, timeout, unexpected HTTP status, too many redirects or a refused redirect |
+    /// | 4 | checksum or size mismatch, or an unusable checksum file |
+    /// | 5 | file not found |
+    /// | 6 | destination already exists |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RfmError::InvalidArgs(_) | RfmError::Release { .. } => 2,
+            RfmError::Network(_)
+            | RfmError::Timeout { .. }
+            | RfmError::HttpStatus { .. }
+            | RfmError::TooManyRedirects { .. }
+            | RfmError::InsecureRedirect { .. } => 3,
+            RfmError::ChecksumMismatch { .. }
+            | RfmError::ChecksumFile { .. }
+            | RfmError::SizeMismatch { .. } => 4,
+            RfmError::NotFound(_) => 5,
+            RfmError::Io(e) if e.kind() == io::ErrorKind::NotFound => 5,
+            RfmError::DestinationExists(_) => 6,
+            RfmError::Incomplete { .. } | RfmError::SourceNotRemoved { .. } | RfmError::Io(_) => 1,
+        }
+    }
+}
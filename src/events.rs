@@ -0,0 +1,143 @@
+//! Machine-readable progress for `--progress-fd` and `--progress-socket`: while a sink is set,
+//! every download and copy streams newline-delimited JSON events to it for frontends to render.
+
+use indicatif::ProgressBar;
+use log::debug;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// How often a running transfer reports, however fast its bytes arrive.
+const EVENT_INTERVAL: Duration = Duration::from_millis(200);
+
+/// One line of the progress stream:
+///
+/// ```json
+/// {"phase":"copy","source":"a.iso","bytes":1024,"total":4096,"percent":25.0,"done":false}
+/// ```
+#[derive(Serialize)]
+pub struct ProgressEvent<'a> {
+    /// "download", "copy", or "move" for a directory merged into another.
+    pub phase: &'static str,
+    /// URL being downloaded, or path being copied.
+    pub source: &'a str,
+    /// Bytes transferred so far, including any resumed prefix.
+    pub bytes: u64,
+    /// Size of the whole transfer; `null` when the server didn't say.
+    pub total: Option<u64>,
+    /// `bytes` as a share of `total`, to one decimal place; `null` without a total.
+    pub percent: Option<f64>,
+    /// Set on the last event of a transfer, whether it succeeded or not.
+    pub done: bool,
+}
+
+/// Where events go; `None` unless one of the progress options was given.
+static SINK: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+
+/// Sends every following progress event to `sink`.
+pub fn set_sink(sink: Box<dyn Write + Send>) {
+    *SINK.lock().unwrap() = Some(sink);
+}
+
+/// Opens the already-open file descriptor `fd`, as handed over by a parent process.
+#[cfg(unix)]
+pub fn open_fd(fd: u32) -> io::Result<Box<dyn Write + Send>> {
+    // Reopening through /dev/fd keeps the descriptor's ownership with whoever opened it.
+    let file: std::fs::File =
+        std::fs::OpenOptions::new().write(true).open(format!("/dev/fd/{}", fd))?;
+    Ok(Box::new(file))
+}
+
+#[cfg(not(unix))]
+pub fn open_fd(_fd: u32) -> io::Result<Box<dyn Write + Send>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--progress-fd is only supported on Unix, use --progress-socket with a named pipe",
+    ))
+}
+
+/// Connects to the Unix domain socket listening at `path`.
+#[cfg(unix)]
+pub fn open_socket(path: &Path) -> io::Result<Box<dyn Write + Send>> {
+    Ok(Box::new(std::os::unix::net::UnixStream::connect(path)?))
+}
+
+/// Opens the named pipe at `path`, such as `\\.\pipe\rfm-progress`.
+#[cfg(windows)]
+pub fn open_socket(path: &Path) -> io::Result<Box<dyn Write + Send>> {
+    Ok(Box::new(std::fs::OpenOptions::new().write(true).open(path)?))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn open_socket(_path: &Path) -> io::Result<Box<dyn Write + Send>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "--progress-socket is not supported here"))
+}
+
+/// Writes `event` as one line. A frontend that went away only stops the events, not the run.
+fn emit(event: &ProgressEvent) {
+    let mut sink = SINK.lock().unwrap();
+    let Some(writer) = sink.as_mut() else { return };
+    let written: io::Result<()> = serde_json::to_writer(&mut *writer, event)
+        .map_err(io::Error::from)
+        .and_then(|()| writer.write_all(b"\n"))
+        .and_then(|()| writer.flush());
+    if let Err(e) = written {
+        debug!("progress events stopped: {}", e);
+        *sink = None;
+    }
+}
+
+/// Reports a bar's position every `EVENT_INTERVAL` until dropped, then sends the final event.
+pub(crate) struct Tracker {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the thread; joining makes sure the last event is out.
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Starts reporting `pb` as a `phase` transfer of `source`, for as long as the returned
+/// tracker lives. Without a sink this does nothing.
+pub(crate) fn track(pb: &ProgressBar, phase: &'static str, source: &str) -> Tracker {
+    if SINK.lock().unwrap().is_none() {
+        return Tracker { stop: None, thread: None };
+    }
+
+    let (stop, stopped) = mpsc::channel::<()>();
+    let pb: ProgressBar = pb.clone();
+    let source: String = source.to_string();
+    let thread: thread::JoinHandle<()> = thread::spawn(move || {
+        loop {
+            let done: bool =
+                !matches!(stopped.recv_timeout(EVENT_INTERVAL), Err(RecvTimeoutError::Timeout));
+            let (bytes, total): (u64, Option<u64>) = (pb.position(), pb.length());
+            emit(&ProgressEvent {
+                phase,
+                source: &source,
+                bytes,
+                total,
+                percent: total.map(|total| match total {
+                    0 => 100.0,
+                    total => (bytes as f64 * 1000.0 / total as f64).round() / 10.0,
+                }),
+                done,
+            });
+            if done {
+                break;
+            }
+        }
+    });
+
+    Tracker { stop: Some(stop), thread: Some(thread) }
+}
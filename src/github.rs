@@ -0,0 +1,171 @@
+//! `--github`: finding the download URL of an asset in a GitHub release, so it can be installed
+//! without copying the URL off the release page.
+
+use crate::error::RfmError;
+use crate::install::{build_client, timeout_error, InstallOptions};
+use glob::Pattern;
+use log::debug;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{HeaderValue, ACCEPT, AUTHORIZATION};
+use serde::Deserialize;
+
+/// Asked when `GITHUB_API_URL` doesn't name another API, as GitHub Actions does on GitHub
+/// Enterprise Server.
+const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// Escaped in a tag put into the API path: everything but what RFC 3986 leaves unreserved.
+const TAG_ESCAPES: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// The parts of a release the API describes that picking an asset needs.
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    /// The asset through the API, which a token can download from a private repository.
+    url: String,
+    /// The public link on the release page.
+    browser_download_url: String,
+}
+
+/// The one asset `release_asset()` settled on.
+#[derive(Debug)]
+pub struct ReleaseAsset {
+    /// The file name it was published under.
+    pub name: String,
+    /// Where to download it from.
+    pub url: String,
+}
+
+/// Finds the asset of `repo`'s release tagged `tag`, or of its latest release, whose name
+/// matches the glob `pattern`; without a pattern the release must have just one asset.
+///
+/// `GITHUB_TOKEN` is sent as a bearer token unless `--header` already gave an `Authorization`.
+/// With a token the asset is downloaded through the API, which redirects to the file itself, so
+/// `opts.headers` gains the `Accept` that asks it to.
+pub fn release_asset(
+    repo: &str,
+    tag: Option<&str>,
+    pattern: Option<&str>,
+    opts: &mut InstallOptions,
+) -> Result<ReleaseAsset, RfmError> {
+    let fail = |reason: String| RfmError::Release { repo: repo.to_string(), reason };
+    let pattern: Option<Pattern> = pattern
+        .map(Pattern::new)
+        .transpose()
+        .map_err(|e| RfmError::InvalidArgs(format!("invalid --asset pattern: {}", e)))?;
+
+    if !opts.headers.contains_key(AUTHORIZATION)
+        && let Ok(token) = std::env::var("GITHUB_TOKEN")
+        && !token.is_empty()
+    {
+        let value: HeaderValue = HeaderValue::from_str(&format!("Bearer {}", token)).map_err(|_| {
+            RfmError::InvalidArgs("GITHUB_TOKEN is not a valid header value".into())
+        })?;
+        debug!("authenticating to the GitHub API with GITHUB_TOKEN");
+        opts.headers.insert(AUTHORIZATION, value);
+    }
+    let authorized: bool = opts.headers.contains_key(AUTHORIZATION);
+
+    let client: Client = build_client(opts)?;
+    let release: Release = fetch_release(&client, repo, tag, opts, &fail)?;
+    let names: Vec<&str> = release.assets.iter().map(|asset| asset.name.as_str()).collect();
+    let matching: Vec<&Asset> = release
+        .assets
+        .iter()
+        .filter(|asset| pattern.as_ref().is_none_or(|pattern| pattern.matches(&asset.name)))
+        .collect();
+    let asset: &Asset = match matching.as_slice() {
+        [asset] => asset,
+        [] if names.is_empty() => {
+            return Err(fail(format!("release {} has no assets", release.tag_name)));
+        }
+        [] => {
+            return Err(fail(format!(
+                "no asset of release {} matches {:?}; it has {}",
+                release.tag_name,
+                pattern.map_or(String::new(), |pattern| pattern.to_string()),
+                names.join(", ")
+            )));
+        }
+        several => {
+            let names: Vec<&str> = several.iter().map(|asset| asset.name.as_str()).collect();
+            return Err(fail(format!(
+                "{} assets of release {} match, narrow it down with --asset: {}",
+                several.len(),
+                release.tag_name,
+                names.join(", ")
+            )));
+        }
+    };
+
+    debug!("release {} of {} has asset {}", release.tag_name, repo, asset.name);
+    let url: String = if authorized {
+        // The API answers with the file itself, by way of a redirect, only when asked for it.
+        // reqwest drops the token on that redirect to another host.
+        opts.headers.insert(ACCEPT, HeaderValue::from_static("application/octet-stream"));
+        asset.url.clone()
+    } else {
+        asset.browser_download_url.clone()
+    };
+    Ok(ReleaseAsset { name: asset.name.clone(), url })
+}
+
+/// Asks the API for the release tagged `tag`, or the latest one.
+fn fetch_release(
+    client: &Client,
+    repo: &str,
+    tag: Option<&str>,
+    opts: &InstallOptions,
+    fail: &dyn Fn(String) -> RfmError,
+) -> Result<Release, RfmError> {
+    let api: String = std::env::var("GITHUB_API_URL")
+        .ok()
+        .filter(|api| !api.is_empty())
+        .unwrap_or_else(|| DEFAULT_API_URL.into());
+    let which: String = match tag {
+        // A tag may hold slashes, which would otherwise read as more of the path.
+        Some(tag) => format!("tags/{}", utf8_percent_encode(tag, TAG_ESCAPES)),
+        None => "latest".into(),
+    };
+    let url: String = format!("{}/repos/{}/releases/{}", api.trim_end_matches('/'), repo, which);
+
+    debug!("GET {}", url);
+    let response: Response = client
+        .get(&url)
+        .headers(opts.headers.clone())
+        .header(ACCEPT, "application/vnd.github+json")
+        .header("X-GitHub-Api-Version", "2022-11-28")
+        .send()
+        .map_err(|e| timeout_error(e, opts))?;
+    let status: StatusCode = response.status();
+    let limited: bool =
+        response.headers().get("x-ratelimit-remaining").is_some_and(|left| left == "0");
+    match status {
+        // A private repository looks the same as a missing one without a token.
+        StatusCode::NOT_FOUND => {
+            let missing: String = match tag {
+                Some(tag) => format!("there is no release tagged {}", tag),
+                None => "there is no latest release".into(),
+            };
+            let hint: &str = "if the repository is private, set GITHUB_TOKEN";
+            return Err(fail(format!("{}; {}", missing, hint)));
+        }
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS if limited => {
+            let reason: &str = "the GitHub API rate limit is used up, set GITHUB_TOKEN to raise it";
+            return Err(fail(reason.into()));
+        }
+        status if !status.is_success() => {
+            return Err(RfmError::HttpStatus { status, url });
+        }
+        _ => {}
+    }
+    let text: String = response.text()?;
+    serde_json::from_str(&text).map_err(|e| fail(format!("unexpected answer from {}: {}", url, e)))
+}
@@ -0,0 +1,64 @@
+//! Ctrl-C and `--deadline` cleanup: operations register the files they are part-way through
+//! writing, so an interrupt can remove them instead of leaving a half-written file behind.
+
+use log::debug;
+use std::fs::remove_file;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Files currently being written that would be incomplete if the process stopped now, each
+/// with whether a later run can resume it.
+static PARTIAL_FILES: Mutex<Vec<(PathBuf, bool)>> = Mutex::new(Vec::new());
+
+/// Keeps a path on the list `cleanup()` removes for as long as it is alive, which is for as long
+/// as the file is incomplete.
+pub(crate) struct PartialFile(PathBuf);
+
+impl PartialFile {
+    pub(crate) fn new(path: &Path) -> Self {
+        Self::register(path, false)
+    }
+
+    /// A download a later run can pick up, so only `cleanup_all()` removes it.
+    pub(crate) fn resumable(path: &Path) -> Self {
+        Self::register(path, true)
+    }
+
+    fn register(path: &Path, resumable: bool) -> Self {
+        PARTIAL_FILES.lock().unwrap().push((path.to_path_buf(), resumable));
+        PartialFile(path.to_path_buf())
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        let mut files = PARTIAL_FILES.lock().unwrap();
+        if let Some(index) = files.iter().position(|(path, _)| *path == self.0) {
+            files.remove(index);
+        }
+    }
+}
+
+/// Removes every file an operation is still writing, except downloads a later run can resume,
+/// for the Ctrl-C handler to call just before the process exits.
+pub fn cleanup() {
+    remove_partial_files(false);
+}
+
+/// Like `cleanup()`, but removes resumable downloads too, since a run stopped by `--deadline`
+/// shouldn't leave anything behind.
+pub fn cleanup_all() {
+    remove_partial_files(true);
+}
+
+fn remove_partial_files(resumable_too: bool) {
+    // Take the list so a second Ctrl-C doesn't try the same files again.
+    let files: Vec<(PathBuf, bool)> = std::mem::take(&mut *PARTIAL_FILES.lock().unwrap());
+    for (path, resumable) in files {
+        if resumable && !resumable_too {
+            continue;
+        }
+        debug!("removing partial file {}", path.display());
+        let _ = remove_file(&path);
+    }
+}
@@ -0,0 +1,40 @@
+//! The file operations behind the `rfm` command line tool: downloading, deleting, moving and
+//! copying, each driven by a plain options struct.
+
+#[macro_use]
+pub mod output;
+
+mod archive;
+pub mod audit;
+mod cache;
+mod decompress;
+mod ranges;
+pub mod checksum;
+pub mod delete;
+pub mod doctor;
+pub mod error;
+pub mod events;
+pub mod github;
+pub mod install;
+pub mod interrupt;
+pub mod transfer;
+
+pub use checksum::{print_digest, Checksum, HashAlgorithm};
+pub use delete::{
+    describe_targets, list_tree, modified_within, parse_age, parse_size, size_within, uninstall,
+    DeleteOptions,
+};
+pub use doctor::diagnose;
+pub use error::RfmError;
+pub use github::{release_asset, ReleaseAsset};
+pub use install::{install, parse_header, parse_mode, parse_proxy, parse_rate, InstallOptions};
+pub use transfer::{
+    copy_path, move_file, plan_renames, rename_target, MoveOptions, OverwritePolicy,
+};
+
+/// The crate version with the commit and date it was built from, as `--version` prints it.
+pub const VERSION: &str =
+    concat!(env!("CARGO_PKG_VERSION"), " (", env!("RFM_COMMIT"), " ", env!("RFM_BUILD_DATE"), ")");
+
+/// Size of each chunk read from the network and handed to the writer.
+pub(crate) const CHUNK_SIZE: usize = 8192;
@@ -0,0 +1,432 @@
+//! Terminal output shared by every mode: colour, progress bars, prompts and the switches
+//! that silence them.
+
+use ansi_term::Colour::*;
+use ansi_term::{ANSIString, Colour, Style};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle, WeakProgressBar};
+use std::borrow::Cow;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+/// Set by `--json`: the final report is the only thing written to stdout.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Whether human-readable output is being suppressed for `--json`.
+pub fn json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Silences every human-readable message, leaving stdout to the caller.
+pub fn set_json_output(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+/// Set by `--quiet`: nothing is printed unless something goes wrong.
+static QUIET_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Whether everything but errors is being suppressed for `--quiet`.
+pub fn quiet_output() -> bool {
+    QUIET_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// Silences progress bars, success messages and warnings, leaving only errors.
+pub fn set_quiet_output(enabled: bool) {
+    QUIET_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+/// Set while a download streams to stdout (`-i -`), so messages move to stderr instead.
+static STDOUT_IS_DATA: AtomicBool = AtomicBool::new(false);
+
+/// Whether stdout carries downloaded bytes rather than messages.
+pub fn stdout_is_data() -> bool {
+    STDOUT_IS_DATA.load(Ordering::Relaxed)
+}
+
+/// Keeps every human-readable message off stdout, leaving it to the downloaded bytes.
+pub fn set_stdout_is_data(enabled: bool) {
+    STDOUT_IS_DATA.store(enabled, Ordering::Relaxed);
+}
+
+/// Cleared by `--no-progress`; bars are also hidden whenever the JSON report owns the output
+/// or `--quiet` is set.
+static PROGRESS_OUTPUT: AtomicBool = AtomicBool::new(true);
+
+pub(crate) fn progress_output() -> bool {
+    PROGRESS_OUTPUT.load(Ordering::Relaxed) && !json_output() && !quiet_output()
+}
+
+/// Shows or hides the progress bars drawn for downloads and copies.
+pub fn set_progress_output(enabled: bool) {
+    PROGRESS_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+/// Layout shared by every bar that tracks a known number of bytes.
+const BYTES_TEMPLATE: &str = "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] \
+                              {bytes}/{total_bytes} ({bytes_per_sec}, {eta})";
+
+/// Fill characters of those bars: filled, then the head, then empty.
+const BYTES_CHARS: &str = "#>-";
+
+/// Set by `--progress-template` and `--progress-chars`, in place of the two above.
+static BYTES_STYLE: Mutex<(Option<String>, Option<String>)> = Mutex::new((None, None));
+
+/// Draws every later bar that counts bytes with `template`, checked here so a bad one is
+/// refused up front instead of failing the first bar.
+pub fn set_progress_template(template: &str) -> Result<(), String> {
+    ProgressStyle::with_template(template)
+        .map_err(|e| format!("invalid --progress-template {:?}: {}", template, e))?;
+    BYTES_STYLE.lock().unwrap().0 = Some(template.to_string());
+    Ok(())
+}
+
+/// Fills every later bar that counts bytes with `chars`. indicatif panics unless there are at
+/// least two, all of the same width, so anything else is refused.
+pub fn set_progress_chars(chars: &str) -> Result<(), String> {
+    let widths: Vec<usize> =
+        chars.chars().map(|c| console::measure_text_width(c.encode_utf8(&mut [0; 4]))).collect();
+    if widths.len() < 2 {
+        return Err(format!("invalid --progress-chars {:?}: at least two are needed", chars));
+    }
+    if widths.iter().any(|width| *width != widths[0]) || widths[0] == 0 {
+        return Err(format!("invalid --progress-chars {:?}: they must all be as wide", chars));
+    }
+    BYTES_STYLE.lock().unwrap().1 = Some(chars.to_string());
+    Ok(())
+}
+
+/// Set while `--jobs` runs downloads side by side, so their bars stack instead of overwriting each other.
+static PROGRESS_GROUP: Mutex<Option<MultiProgress>> = Mutex::new(None);
+
+/// The most recent bar drawn outside a group; weak, so a finished bar is simply dropped.
+static LONE_BAR: Mutex<Option<WeakProgressBar>> = Mutex::new(None);
+
+/// Starts or ends a group in which every new bar is stacked under the others, for work
+/// running side by side.
+pub fn group_progress(enabled: bool) {
+    *PROGRESS_GROUP.lock().unwrap() = enabled.then(MultiProgress::new);
+}
+
+/// Wipes whatever bars are on screen, for when the process is about to exit under them.
+pub fn clear_progress() {
+    match PROGRESS_GROUP.lock().unwrap().as_ref() {
+        Some(group) => {
+            let _ = group.clear();
+        }
+        // A lone bar only ever occupies the current line.
+        None if progress_output() => {
+            if let Some(bar) = LONE_BAR.lock().unwrap().as_ref().and_then(|bar| bar.upgrade()) {
+                bar.finish_and_clear();
+            }
+            let _ = console::Term::stderr().clear_line();
+        }
+        None => {}
+    }
+}
+
+/// Attaches `pb` to the active progress group, if there is one, or remembers it as the lone
+/// bar that messages have to be printed around.
+pub(crate) fn grouped(pb: ProgressBar) -> ProgressBar {
+    match PROGRESS_GROUP.lock().unwrap().as_ref() {
+        Some(group) => group.add(pb),
+        None => {
+            *LONE_BAR.lock().unwrap() = Some(pb.downgrade());
+            pb
+        }
+    }
+}
+
+/// Finishes `pb`, leaving its last frame on screen. indicatif leaves the cursor at the end of
+/// a lone bar's line, so this moves past it for whatever is printed next.
+pub(crate) fn finish_progress(pb: &ProgressBar) {
+    pb.finish();
+    if !pb.is_hidden() && PROGRESS_GROUP.lock().unwrap().is_none() {
+        eprintln!();
+    }
+}
+
+/// Runs `print` with any bars cleared from the terminal, redrawing them afterwards, then
+/// flushes stdout so the message is out before the next frame is drawn.
+#[doc(hidden)]
+pub fn suspend_progress<F: FnOnce()>(print: F) {
+    // Clone the handles so no lock is held while the bars redraw.
+    let group: Option<MultiProgress> = PROGRESS_GROUP.lock().unwrap().clone();
+    let lone: Option<ProgressBar> = LONE_BAR.lock().unwrap().as_ref().and_then(|bar| bar.upgrade());
+    match (group, lone) {
+        (Some(group), _) => group.suspend(print),
+        (None, Some(bar)) if !bar.is_finished() => bar.suspend(print),
+        _ => print(),
+    }
+    let _ = io::stdout().flush();
+}
+
+/// A bar counting up to `total` bytes, or a hidden one when progress output is off; hidden
+/// bars still count, for progress events.
+pub(crate) fn bytes_bar(total: u64) -> ProgressBar {
+    if !progress_output() {
+        return ProgressBar::with_draw_target(Some(total), ProgressDrawTarget::hidden());
+    }
+    let pb: ProgressBar = ProgressBar::new(total);
+    let style = BYTES_STYLE.lock().unwrap();
+    let template: &str = style.0.as_deref().unwrap_or(BYTES_TEMPLATE);
+    pb.set_style(
+        ProgressStyle::with_template(template)
+            .expect("templates are checked before they are set")
+            .progress_chars(style.1.as_deref().unwrap_or(BYTES_CHARS)),
+    );
+    grouped(pb)
+}
+
+/// Cleared by `--no-color`, `NO_COLOR` or a non-terminal stdout.
+static COLOR_OUTPUT: AtomicBool = AtomicBool::new(true);
+
+/// Styles `text` in `colour` when colour output is enabled.
+pub fn paint<'a, I: Into<Cow<'a, str>>>(colour: Colour, text: I) -> ANSIString<'a> {
+    if COLOR_OUTPUT.load(Ordering::Relaxed) {
+        colour.paint(text)
+    } else {
+        Style::new().paint(text)
+    }
+}
+
+/// Decides once at startup whether output is coloured, following the NO_COLOR convention.
+pub fn init_color(no_color: bool) {
+    let no_color_env: bool = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    // Messages go wherever stdout's data doesn't, so that is the stream to ask about.
+    let terminal: bool =
+        if stdout_is_data() { io::stderr().is_terminal() } else { io::stdout().is_terminal() };
+    let enabled: bool = !no_color && !no_color_env && terminal;
+
+    COLOR_OUTPUT.store(enabled, Ordering::Relaxed);
+    // Progress bars style their templates through `console` rather than `ansi_term`.
+    console::set_colors_enabled(enabled);
+    console::set_colors_enabled_stderr(enabled);
+}
+
+/// `println!` for human-readable output, silenced when `--json` owns stdout and by `--quiet`,
+/// and sent to stderr while stdout carries a download.
+#[macro_export]
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !$crate::output::json_output() && !$crate::output::quiet_output() {
+            $crate::output::suspend_progress(|| {
+                if $crate::output::stdout_is_data() {
+                    eprintln!($($arg)*)
+                } else {
+                    println!($($arg)*)
+                }
+            });
+        }
+    };
+}
+
+/// `eprintln!` for errors, which `--quiet` still lets through.
+#[macro_export]
+macro_rules! say_err {
+    ($($arg:tt)*) => {
+        if !$crate::output::json_output() {
+            $crate::output::suspend_progress(|| eprintln!($($arg)*));
+        }
+    };
+}
+
+/// `eprintln!` for warnings and notes about a run that is still going, silenced like `say!`.
+#[macro_export]
+macro_rules! notice {
+    ($($arg:tt)*) => {
+        if !$crate::output::json_output() && !$crate::output::quiet_output() {
+            $crate::output::suspend_progress(|| eprintln!($($arg)*));
+        }
+    };
+}
+
+/// Prints a line describing an action `--dry-run` skipped.
+pub(crate) fn dry_run_note(message: &str) {
+    say!("{} {}", paint(Cyan, "[dry-run]"), message);
+}
+
+/// Set by `--confirm-timeout`, in seconds; zero waits for an answer as long as it takes.
+static CONFIRM_TIMEOUT: AtomicU64 = AtomicU64::new(0);
+
+/// Makes every later prompt give up after `timeout`, as if the answer was "no".
+pub fn set_confirm_timeout(timeout: Option<Duration>) {
+    CONFIRM_TIMEOUT.store(timeout.map_or(0, |timeout| timeout.as_secs()), Ordering::Relaxed);
+}
+
+/// Lines read from stdin by a thread of their own, so a prompt can stop waiting for one. The
+/// thread lives as long as the process: a `read_line` that timed out can't be cancelled.
+static ANSWERS: OnceLock<Mutex<Receiver<io::Result<String>>>> = OnceLock::new();
+
+fn answers() -> MutexGuard<'static, Receiver<io::Result<String>>> {
+    let answers = ANSWERS.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<io::Result<String>>();
+        thread::spawn(move || {
+            loop {
+                let mut line: String = String::new();
+                let read: io::Result<usize> = io::stdin().read_line(&mut line);
+                let done: bool = !matches!(read, Ok(n) if n > 0);
+                if sender.send(read.map(|_| line)).is_err() || done {
+                    break;
+                }
+            }
+        });
+        Mutex::new(receiver)
+    });
+    answers.lock().unwrap()
+}
+
+/// Reads one answer, or `None` once `timeout` passes without one.
+fn read_answer(timeout: Duration) -> io::Result<Option<String>> {
+    let answers = answers();
+    // A late answer to a prompt that already timed out isn't an answer to this one.
+    while answers.try_recv().is_ok() {}
+    match answers.recv_timeout(timeout) {
+        Ok(answer) => answer.map(Some),
+        Err(RecvTimeoutError::Timeout) => Ok(None),
+        // stdin reached its end, which reads as an empty line.
+        Err(RecvTimeoutError::Disconnected) => Ok(Some(String::new())),
+    }
+}
+
+/// Asks `question` on stdout, offering `choices`, and reads the answer in lowercase. With
+/// `--confirm-timeout`, no answer in time is `None`, noted as taken to mean `on_timeout`.
+fn ask(question: &str, choices: &str, on_timeout: &str) -> io::Result<Option<String>> {
+    // Refuse instead of blocking on a prompt nobody can answer.
+    if !io::stdin().is_terminal() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "stdin is not a terminal, cannot ask for confirmation",
+        ));
+    }
+
+    // Printed even under `--quiet`: a prompt nobody can see can't be answered.
+    let timeout: u64 = CONFIRM_TIMEOUT.load(Ordering::Relaxed);
+    if timeout == 0 {
+        suspend_progress(|| println!("{} ({})?", question, choices));
+    } else {
+        suspend_progress(|| {
+            println!("{} ({}, {} in {}s)?", question, choices, on_timeout, timeout)
+        });
+    }
+
+    // Normalize user confirmation to make matching case-insensitive.
+    let mut confirmation: String = String::new();
+    if timeout == 0 {
+        io::stdin().read_line(&mut confirmation)?;
+    } else {
+        match read_answer(Duration::from_secs(timeout))? {
+            Some(answer) => confirmation = answer,
+            None => {
+                // Shown even under `--quiet`, like the prompt it answers.
+                let note: ANSIString = paint(Yellow, "Note:");
+                suspend_progress(|| {
+                    eprintln!("{} no answer within {}s, taking it as {}", note, timeout, on_timeout)
+                });
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(confirmation.trim().to_lowercase()))
+}
+
+/// Asks `question` on stdout and reads the answer; anything but "n" or "no" counts as yes.
+/// With `--confirm-timeout`, no answer in time counts as no.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    Ok(ask(question, "y/n", "no")?.is_some_and(|answer| answer != "n" && answer != "no"))
+}
+
+/// An answer to one of the `--confirm-each` questions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Answer {
+    Yes,
+    No,
+    /// Yes to this and everything after it, without asking again.
+    All,
+    /// No to this and everything after it.
+    Quit,
+}
+
+/// Asks `question` for `--confirm-each`. Unlike `confirm()`, only "y" or "yes" counts as yes,
+/// and anything not understood as no; no answer in time quits.
+pub fn confirm_each(question: &str) -> io::Result<Answer> {
+    Ok(match ask(question, "y/n/a/q", "quit")?.as_deref() {
+        None | Some("q" | "quit") => Answer::Quit,
+        Some("y" | "yes") => Answer::Yes,
+        Some("a" | "all") => Answer::All,
+        Some(_) => Answer::No,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_is_styled_with_colour_off() {
+        COLOR_OUTPUT.store(true, Ordering::Relaxed);
+        assert!(paint(Red, "Error:").to_string().contains('\x1b'));
+
+        COLOR_OUTPUT.store(false, Ordering::Relaxed);
+        let painted: String = format!("{} {}", paint(Red, "Error:"), paint(Green, "Success"));
+        assert_eq!(painted, "Error: Success");
+    }
+
+    /// A terminal that logs what is done to it, for checking what a bar draws when.
+    #[derive(Debug, Clone)]
+    struct Recorder(std::sync::Arc<Mutex<Vec<String>>>);
+
+    impl indicatif::TermLike for Recorder {
+        fn width(&self) -> u16 {
+            80
+        }
+        fn move_cursor_up(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_down(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_right(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn move_cursor_left(&self, _: usize) -> io::Result<()> {
+            Ok(())
+        }
+        fn write_line(&self, s: &str) -> io::Result<()> {
+            self.0.lock().unwrap().push(format!("draw {}", s));
+            Ok(())
+        }
+        fn write_str(&self, s: &str) -> io::Result<()> {
+            self.0.lock().unwrap().push(format!("draw {}", s));
+            Ok(())
+        }
+        fn clear_line(&self) -> io::Result<()> {
+            self.0.lock().unwrap().push("clear".into());
+            Ok(())
+        }
+        fn flush(&self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn messages_are_printed_between_bar_frames() {
+        let log: std::sync::Arc<Mutex<Vec<String>>> = Default::default();
+        let target: ProgressDrawTarget =
+            ProgressDrawTarget::term_like(Box::new(Recorder(log.clone())));
+        let pb: ProgressBar = grouped(ProgressBar::with_draw_target(Some(10), target));
+        pb.set_style(ProgressStyle::with_template("{pos}/{len}").unwrap());
+        pb.set_position(5);
+        pb.tick();
+
+        suspend_progress(|| log.lock().unwrap().push("message".into()));
+        let log: Vec<String> = log.lock().unwrap().clone();
+        let message: usize = log.iter().position(|entry| entry == "message").unwrap();
+        // The bar is drawn, wiped for the message, and drawn again under it.
+        assert!(log[..message].iter().any(|entry| entry == "draw 5/10"), "{:?}", log);
+        assert_eq!(log[message - 1], "clear", "{:?}", log);
+        assert!(log[message + 1..].iter().any(|entry| entry == "draw 5/10"), "{:?}", log);
+    }
+}
@@ -0,0 +1,209 @@
+//! `--parallel-chunks`: one download split into byte ranges fetched side by side, each written
+//! at its own offset of a file set to its full size up front.
+
+use crate::cache::CacheEntry;
+use crate::error::RfmError;
+use crate::install::{body_error, timeout_error, InstallOptions, RateLimiter};
+use indicatif::ProgressBar;
+use log::debug;
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+/// A range smaller than this isn't worth a connection of its own.
+const MIN_RANGE: u64 = 1024 * 1024;
+
+/// What asking for the first byte of a download found out about fetching it in ranges.
+pub(crate) enum Probe {
+    /// The cached validators still match; there is nothing to download.
+    NotModified,
+    /// The server serves byte ranges of a file big enough to split.
+    Ranges {
+        /// URL the file is served from, after redirects, so each range skips them.
+        url: String,
+        total: u64,
+        cache: Option<CacheEntry>,
+    },
+    /// One stream it is: no ranges, no known size, or too small a file.
+    Single,
+}
+
+/// What every range of one download shares.
+struct Shared<'a> {
+    client: &'a Client,
+    url: &'a str,
+    path: &'a Path,
+    opts: &'a InstallOptions<'a>,
+    pb: &'a ProgressBar,
+    /// Set by the first range to fail, which stops the others.
+    failed: AtomicBool,
+    /// Bytes per second each range may read, its share of `--limit-rate`.
+    rate: Option<u64>,
+}
+
+/// Asks the server for the first byte of `url`, with the same conditional headers the
+/// download would send. Only a server that answers with that byte and the file's size is
+/// trusted with ranges; `Accept-Ranges` alone is sometimes claimed and then ignored.
+pub(crate) fn probe(
+    client: &Client,
+    url: &str,
+    opts: &InstallOptions,
+    cached: Option<&CacheEntry>,
+) -> Result<Probe, RfmError> {
+    let request = client.get(url).headers(opts.headers.clone()).header(RANGE, "bytes=0-0");
+    let request = match cached {
+        Some(cached) => cached.apply(request),
+        None => request,
+    };
+    debug!("GET {} bytes 0-0 to check for range support", url);
+    let response: Response = request.send().map_err(|e| timeout_error(e, opts))?;
+    if cached.is_some() && response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(Probe::NotModified);
+    }
+    // The single stream reports a failing status properly.
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        debug!("{} answered {} to a range, downloading as one stream", url, response.status());
+        return Ok(Probe::Single);
+    }
+
+    // `bytes 0-0/<total>`; a `*` total means the size isn't known.
+    let total: Option<u64> = response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|range| range.strip_prefix("bytes 0-0/"))
+        .and_then(|total| total.parse::<u64>().ok());
+    match total {
+        Some(total) if total >= 2 * MIN_RANGE => Ok(Probe::Ranges {
+            url: response.url().to_string(),
+            total,
+            cache: CacheEntry::from_response(url, &response),
+        }),
+        _ => {
+            debug!("{} is too small or of unknown size ({:?}) to split", url, total);
+            Ok(Probe::Single)
+        }
+    }
+}
+
+/// Downloads the `total` bytes of `url` into `path` as up to `chunks` ranges at once, all
+/// counted on `pb`. The first range to fail stops the others.
+pub(crate) fn fetch(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    total: u64,
+    chunks: usize,
+    opts: &InstallOptions,
+    pb: &ProgressBar,
+) -> Result<(), RfmError> {
+    let count: u64 = (chunks as u64).min(total / MIN_RANGE).max(1);
+    let size: u64 = total.div_ceil(count);
+    debug!("fetching {} bytes as {} ranges of up to {} bytes", total, count, size);
+    // Every range writes into its own part of the file, so it has to be that long already.
+    File::create(path)?.set_len(total)?;
+
+    let shared: Shared = Shared {
+        client,
+        url,
+        path,
+        opts,
+        pb,
+        failed: AtomicBool::new(false),
+        // The limit is on the whole download, so each range gets its share.
+        rate: opts.limit_rate.map(|rate| (rate / count).max(1)),
+    };
+    let results: Vec<Result<(), RfmError>> = thread::scope(|scope| {
+        let handles: Vec<thread::ScopedJoinHandle<Result<(), RfmError>>> = (0..count)
+            .map(|index| {
+                let start: u64 = index * size;
+                let end: u64 = (start + size).min(total) - 1;
+                let shared: &Shared = &shared;
+                scope.spawn(move || {
+                    let result: Result<(), RfmError> = fetch_range(shared, start, end);
+                    if result.is_err() {
+                        shared.failed.store(true, Ordering::Relaxed);
+                    }
+                    result
+                })
+            })
+            .collect();
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    });
+    results.into_iter().collect::<Result<(), RfmError>>()?;
+
+    // On disk before the rename, so a crash can't leave a complete-looking file with holes.
+    OpenOptions::new().write(true).open(path)?.sync_all()?;
+    Ok(())
+}
+
+/// Fetches bytes `start..=end` of the download into the same place in its file, until done or
+/// until another range gives up.
+fn fetch_range(shared: &Shared, start: u64, end: u64) -> Result<(), RfmError> {
+    let Shared { client, url, path, opts, pb, ref failed, rate } = *shared;
+    debug!("GET {} bytes {}-{}", url, start, end);
+    let mut response: Response = client
+        .get(url)
+        .headers(opts.headers.clone())
+        .header(RANGE, format!("bytes={}-{}", start, end))
+        .send()
+        .map_err(|e| timeout_error(e, opts))?;
+    if !response.status().is_success() {
+        return Err(RfmError::HttpStatus { status: response.status(), url: url.to_string() });
+    }
+    // A whole file sent in answer, or the wrong part of it, can't go at this offset.
+    let answered: Option<&str> =
+        response.headers().get(CONTENT_RANGE).and_then(|value| value.to_str().ok());
+    let expected: String = format!("bytes {}-{}/", start, end);
+    if response.status() != StatusCode::PARTIAL_CONTENT
+        || !answered.is_some_and(|range| range.starts_with(&expected))
+    {
+        return Err(RfmError::Io(io::Error::other(format!(
+            "{} answered {} with range {:?} when asked for bytes {}-{}",
+            url,
+            response.status(),
+            answered,
+            start,
+            end
+        ))));
+    }
+
+    let mut file: File = OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut limiter: Option<RateLimiter> = rate.map(RateLimiter::new);
+    let mut buffer: [u8; crate::CHUNK_SIZE] = [0; crate::CHUNK_SIZE];
+    let length: u64 = end - start + 1;
+    let mut written: u64 = 0;
+    while written < length {
+        // Whichever range failed reports why; the rest just stop.
+        if failed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let n: usize = match response.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => return Err(body_error(e, opts.read_timeout)),
+        };
+        // Nothing past the end of the range belongs here, even from a server that sends it.
+        let n: usize = n.min((length - written) as usize);
+        file.write_all(&buffer[..n])?;
+        written += n as u64;
+        pb.inc(n as u64);
+        if let Some(limiter) = limiter.as_mut() {
+            limiter.take(n);
+        }
+    }
+    // Short like a body cut off early, so it is retried the same way.
+    if written != length {
+        return Err(RfmError::Io(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("{} sent {} of the {} bytes in range {}-{}", url, written, length, start, end),
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,1129 @@
+//! Move and copy modes.
+
+use crate::audit;
+use crate::error::RfmError;
+use crate::events::{track, Tracker};
+use crate::interrupt::PartialFile;
+use crate::output::{bytes_bar, confirm, dry_run_note, paint};
+use ansi_term::Colour::*;
+use indicatif::{HumanBytes, ProgressBar};
+use log::debug;
+use std::fs::{
+    create_dir_all, metadata, remove_dir_all, remove_file, rename, symlink_metadata, File,
+    FileTimes, Metadata, Permissions,
+};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// What install, move and copy do with a destination that already exists.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Refuse with `RfmError::DestinationExists`; the default.
+    Fail,
+    /// Replace it, for `--force`.
+    Overwrite,
+    /// Leave it alone and succeed without doing anything, for `--no-clobber`.
+    Skip,
+    /// Rename it by appending this suffix before the new file takes its place, for `--backup`.
+    Backup(String),
+    /// Ask first, for `--interactive`, then back it up with the suffix if one is given.
+    Prompt { backup: Option<String> },
+    /// Leave it alone and write to the first free `name (n).ext` instead, like a browser does,
+    /// for `--rename-on-conflict`.
+    Rename,
+}
+
+impl OverwritePolicy {
+    /// The suffix an existing destination is renamed with once it is replaced, if any.
+    pub fn backup_suffix(&self) -> Option<&str> {
+        match self {
+            OverwritePolicy::Backup(suffix)
+            | OverwritePolicy::Prompt { backup: Some(suffix) } => Some(suffix),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for `move_file()` and `copy_path()`, resolved from the command line.
+pub struct MoveOptions {
+    /// What to do when the destination already exists.
+    pub overwrite: OverwritePolicy,
+    /// Report the move instead of performing it.
+    pub dry_run: bool,
+    /// Keep timestamps and ownership whenever data is copied.
+    pub preserve: bool,
+    /// Copy what symlinks inside a directory point to instead of recreating the links, and
+    /// move what a symlinked source points to rather than the link.
+    pub dereference: bool,
+    /// Create the destination's missing parent directories.
+    pub mkdir: bool,
+    /// When a move across filesystems can't remove the original, keep the copy alongside it
+    /// instead of removing the copy again.
+    pub keep_copy_on_error: bool,
+}
+
+/// Carries timestamps and, on Unix, ownership from `source` over to a fresh copy.
+fn copy_metadata(source: &std::fs::Metadata, dest: &File) -> io::Result<()> {
+    dest.set_times(
+        FileTimes::new()
+            .set_accessed(source.accessed()?)
+            .set_modified(source.modified()?),
+    )?;
+
+    // Only root can give files away, so like `mv` settle for our own ownership otherwise.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match std::os::unix::fs::fchown(dest, Some(source.uid()), Some(source.gid())) {
+            Err(e) if e.kind() != io::ErrorKind::PermissionDenied => return Err(e),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Streams `from` into `to` and copies its permission bits, plus timestamps and ownership
+/// when `preserve` is set; a failed copy removes the partial `to`.
+pub(crate) fn copy_file(from: &Path, to: &Path, preserve: bool) -> io::Result<u64> {
+    // Cleared once done so the bar doesn't linger above the success message.
+    let pb: ProgressBar = bytes_bar(metadata(from)?.len());
+    let _events: Tracker = track(&pb, "copy", &from.to_string_lossy());
+    let copied: io::Result<u64> = copy_file_with(from, to, preserve, &pb);
+    pb.finish_and_clear();
+    copied
+}
+
+/// `copy_file()`, advancing `pb` rather than a bar of its own.
+fn copy_file_with(from: &Path, to: &Path, preserve: bool, pb: &ProgressBar) -> io::Result<u64> {
+    let mut source: File = File::open(from)?;
+    let source_meta: std::fs::Metadata = source.metadata()?;
+    let mut dest: File = File::create(to)?;
+    let _partial: PartialFile = PartialFile::new(to);
+
+    let copied: io::Result<u64> = io::copy(&mut pb.wrap_read(&mut source), &mut dest);
+
+    // Ownership goes first: chown clears the setuid and setgid bits the mode may carry.
+    let copied: io::Result<u64> = copied.and_then(|n| {
+        if preserve {
+            copy_metadata(&source_meta, &dest)?;
+        }
+        dest.set_permissions(source_meta.permissions())?;
+        Ok(n)
+    });
+    if copied.is_err() {
+        drop(dest);
+        let _ = remove_file(to);
+    }
+
+    copied
+}
+
+/// Moves a file or directory between filesystems: copy it over, and only delete the original
+/// once the copy is complete.
+fn copy_then_delete(from: &Path, to: &Path, opts: &MoveOptions) -> Result<(), RfmError> {
+    if !relink(from, to)? {
+        let size: u64 = source_size(from, opts.dereference)?;
+        check_space(to, size, true)?;
+        if from.is_dir() {
+            copy_tree_with_progress(from, to, size, opts)?;
+        } else {
+            copy_file(from, to, opts.preserve)?;
+        }
+    }
+    remove_original(from, to, opts.keep_copy_on_error)
+}
+
+/// Recreates the symlink `from` at `to`, pointing wherever it pointed before, for a move
+/// between filesystems; `false` if `from` isn't a link.
+fn relink(from: &Path, to: &Path) -> io::Result<bool> {
+    if !cfg!(unix) || !symlink_metadata(from)?.file_type().is_symlink() {
+        return Ok(false);
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)?;
+    Ok(true)
+}
+
+/// Removes `from` once `to` holds a complete copy of it, finishing a move between filesystems.
+/// If that fails the move is undone by removing the copy, unless `keep_copy` or the original
+/// already lost part of its contents, so it is never reported as done with the source still there.
+fn remove_original(from: &Path, to: &Path, keep_copy: bool) -> Result<(), RfmError> {
+    let is_dir: bool = symlink_metadata(from)?.is_dir();
+    let removed: io::Result<()> = if is_dir { remove_dir_all(from) } else { remove_file(from) };
+    let Err(e) = removed else { return Ok(()) };
+
+    // Once some of a directory's files are gone, the copy holds the only whole tree.
+    let intact: bool = !is_dir || count_files(from).ok() == count_files(to).ok();
+    let mut kept_copy: bool = keep_copy || !intact;
+    if !kept_copy {
+        debug!("cannot remove {}, removing the copy at {}", from.display(), to.display());
+        let undone: io::Result<()> = if is_dir { remove_dir_all(to) } else { remove_file(to) };
+        kept_copy = undone.is_err();
+    }
+    let e: io::Error =
+        if intact { e } else { io::Error::new(e.kind(), format!("{} (part of it is gone)", e)) };
+    Err(RfmError::SourceNotRemoved {
+        from: from.to_path_buf(),
+        to: to.to_path_buf(),
+        kept_copy,
+        source: e,
+    })
+}
+
+/// Applies `policy` to an existing `dest`, the one place every mode decides this; returns
+/// whether to go ahead. Backups are taken by the caller, once the new file is ready.
+pub(crate) fn check_overwrite(dest: &Path, policy: &OverwritePolicy) -> Result<bool, RfmError> {
+    if !dest.exists() {
+        return Ok(true);
+    }
+
+    match policy {
+        // A backup keeps the old file, so it needs no permission to move it aside, and a new
+        // name leaves it alone altogether.
+        OverwritePolicy::Overwrite | OverwritePolicy::Backup(_) | OverwritePolicy::Rename => {
+            Ok(true)
+        }
+        OverwritePolicy::Skip => {
+            say!("Already present, skipping {}", paint(Yellow, dest.display().to_string()));
+            Ok(false)
+        }
+        OverwritePolicy::Prompt { .. } => {
+            let question: String = format!(
+                "{}: Destination {} exists, overwrite?",
+                paint(Yellow, "WARNING"),
+                dest.display()
+            );
+            if !confirm(&question)? {
+                say!("Safely exiting");
+                audit::mark_declined();
+                return Ok(false);
+            }
+            Ok(true)
+        }
+        OverwritePolicy::Fail => Err(RfmError::DestinationExists(dest.to_path_buf())),
+    }
+}
+
+/// `dest`, or when something is already there, the first of `name (1).ext`, `name (2).ext`, ...
+/// that is free. A `.tar.gz` style double extension stays together at the end.
+pub(crate) fn free_name(dest: &Path) -> PathBuf {
+    let taken = |path: &Path| symlink_metadata(path).is_ok();
+    if !taken(dest) {
+        return dest.to_path_buf();
+    }
+    let name: String = dest.file_name().map_or(String::new(), |name| name.to_string_lossy().into());
+    // A leading dot starts a hidden name, not an extension.
+    let start: usize = name.chars().next().map_or(0, char::len_utf8);
+    let split: Option<usize> = name[start..]
+        .find(".tar.")
+        .or_else(|| name[start..].rfind('.'))
+        .map(|split| start + split);
+    let (stem, ext): (&str, &str) = name.split_at(split.unwrap_or(name.len()));
+    (1u64..)
+        .map(|n| dest.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|candidate| !taken(candidate))
+        .expect("some numbered name is free")
+}
+
+/// Where `from` ends up when sent to `to`: inside it, keeping its name, when `to` is an existing
+/// directory or ends in a separator, like `mv` and `cp` do; otherwise `to` itself.
+pub(crate) fn target_in(from: &str, to: &str) -> String {
+    let into_dir: bool = to.ends_with(std::path::is_separator) || Path::new(to).is_dir();
+    match Path::new(from).file_name() {
+        Some(name) if into_dir => Path::new(to).join(name).to_string_lossy().into_owned(),
+        _ => to.to_string(),
+    }
+}
+
+/// The new path `template` gives `path`, in the directory it is already in: `{name}` stands for
+/// the file name up to its last dot and `{ext}` for what follows it.
+pub fn rename_target(path: &str, template: &str) -> Result<String, String> {
+    let file: &Path = Path::new(path);
+    let stem: String = file.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into());
+    let ext: String = file.extension().map_or(String::new(), |ext| ext.to_string_lossy().into());
+
+    let mut name: String = String::new();
+    let mut rest: &str = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end: usize = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| format!("unclosed {{ in --rename template {:?}", template))?;
+        match &rest[start + 1..end] {
+            "name" => name.push_str(&stem),
+            "ext" => name.push_str(&ext),
+            other => {
+                return Err(format!(
+                    "unknown placeholder {{{}}} in --rename template, expected {{name}} or {{ext}}",
+                    other
+                ))
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    name.push_str(rest);
+
+    if name.is_empty() || name.contains(std::path::is_separator) || name == "." || name == ".." {
+        return Err(format!("--rename {:?} would give {} the name {:?}", template, path, name));
+    }
+    Ok(file.with_file_name(name).to_string_lossy().into_owned())
+}
+
+/// Pairs each of `paths` with its new path under `template`. The whole batch is refused if two
+/// files would get the same name, or one would take the name of another still to be renamed.
+pub fn plan_renames(paths: &[String], template: &str) -> Result<Vec<(String, String)>, RfmError> {
+    let mut plan: Vec<(String, String)> = Vec::new();
+    for path in paths {
+        let to: String = rename_target(path, template).map_err(RfmError::InvalidArgs)?;
+        plan.push((path.clone(), to));
+    }
+
+    let mut taken: HashMap<&str, &str> = HashMap::new();
+    for (from, to) in &plan {
+        if let Some(other) = taken.insert(to, from) {
+            return Err(RfmError::InvalidArgs(format!(
+                "{} and {} would both be renamed to {}",
+                other, from, to
+            )));
+        }
+        if from != to && plan.iter().any(|(source, _)| source == to) {
+            return Err(RfmError::InvalidArgs(format!(
+                "{} would be renamed to {}, which is itself being renamed",
+                from, to
+            )));
+        }
+    }
+    Ok(plan)
+}
+
+/// The directory `dest` will be written into.
+pub(crate) fn parent_dir(dest: &Path) -> &Path {
+    match dest.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Makes sure the directory `dest` goes into exists, creating it like `mkdir -p` when `mkdir`
+/// is set; a dry run only reports what it would create.
+pub(crate) fn prepare_parent(dest: &Path, mkdir: bool, dry_run: bool) -> Result<(), RfmError> {
+    let dir: &Path = parent_dir(dest);
+    if dir.is_dir() {
+        return Ok(());
+    }
+    if !mkdir {
+        return Err(RfmError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("parent directory {} does not exist, pass --mkdir to create it", dir.display()),
+        )));
+    }
+
+    // Name the first missing ancestor and everything below it, as `mkdir -pv` would.
+    let mut missing: Vec<&Path> = dir
+        .ancestors()
+        .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        .collect();
+    missing.reverse();
+    if dry_run {
+        for dir in missing {
+            dry_run_note(&format!("Would create directory {}", dir.display()));
+        }
+        return Ok(());
+    }
+    for dir in missing {
+        debug!("creating directory {}", dir.display());
+    }
+    create_dir_all(dir)?;
+    Ok(())
+}
+
+/// Bytes free for unprivileged writes on the filesystem holding `dir`, where that is known.
+pub(crate) fn available_space(dir: &Path) -> Option<u64> {
+    #[cfg(any(unix, windows))]
+    {
+        fs4::available_space(dir).inspect_err(|e| debug!("cannot read free space: {}", e)).ok()
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = dir;
+        None
+    }
+}
+
+/// Fails with an "insufficient disk space" error when `size` bytes won't fit in what is free
+/// on the filesystem `to` goes to, before anything is written there. With `replacing`, an
+/// existing file at `to` counts as free, since the copy truncates it first. A filesystem that
+/// doesn't report its free space is trusted to have room.
+fn check_space(to: &Path, size: u64, replacing: bool) -> io::Result<()> {
+    let Some(free) = available_space(parent_dir(to)) else { return Ok(()) };
+    let reclaimed: u64 = symlink_metadata(to)
+        .ok()
+        .filter(|meta| replacing && meta.is_file())
+        .map_or(0, |meta| meta.len());
+    if size <= free.saturating_add(reclaimed) {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::StorageFull,
+        format!(
+            "insufficient disk space: {} needs {} but only {} is free on {}",
+            to.display(),
+            HumanBytes(size),
+            HumanBytes(free),
+            parent_dir(to).display()
+        ),
+    ))
+}
+
+/// Renames an existing `dest` to `dest` plus `suffix` so the file about to take its place
+/// doesn't destroy it; an older backup with that name is replaced. Returns the backup taken,
+/// for `restore_backup()` should the new file never arrive.
+pub(crate) fn backup_existing(
+    dest: &Path,
+    suffix: &str,
+    dry_run: bool,
+) -> Result<Option<PathBuf>, RfmError> {
+    if std::fs::symlink_metadata(dest).is_err() {
+        return Ok(None);
+    }
+    let mut backup: std::ffi::OsString = dest.as_os_str().to_owned();
+    backup.push(suffix);
+    let backup: PathBuf = PathBuf::from(backup);
+
+    if dry_run {
+        dry_run_note(&format!("Would back up {} -> {}", dest.display(), backup.display()));
+        return Ok(None);
+    }
+
+    // Stop here on failure: the original is still untouched.
+    debug!("backing up {} -> {}", dest.display(), backup.display());
+    rename(dest, &backup).map_err(|e| {
+        io::Error::new(e.kind(), format!("failed to back up {}: {}", dest.display(), e))
+    })?;
+    say!(
+        "{}: {} -> {}",
+        paint(Cyan, "Backup"),
+        paint(Yellow, dest.display().to_string()),
+        paint(Yellow, backup.display().to_string())
+    );
+    Ok(Some(backup))
+}
+
+/// Puts the `backup` of `dest` back after the file meant to replace it failed to arrive,
+/// clearing whatever part of it the failed attempt left behind.
+fn restore_backup(dest: &Path, backup: Option<PathBuf>) {
+    let Some(backup) = backup else { return };
+    debug!("restoring {} from {}", dest.display(), backup.display());
+    let cleared: io::Result<()> = match symlink_metadata(dest) {
+        Ok(meta) if meta.is_dir() => remove_dir_all(dest),
+        Ok(_) => remove_file(dest),
+        Err(_) => Ok(()),
+    };
+    if let Err(e) = cleared.and_then(|()| rename(&backup, dest)) {
+        notice!(
+            "{} could not put {} back from {}: {}",
+            paint(Yellow, "Warning:"),
+            dest.display(),
+            backup.display(),
+            e
+        );
+    }
+}
+
+/// Whether `a` and `b` are one file, through a hard link, a symlink or just a different spelling.
+fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let (Ok(a), Ok(b)) = (metadata(a), metadata(b)) {
+            return a.dev() == b.dev() && a.ino() == b.ino();
+        }
+        false
+    }
+    #[cfg(not(unix))]
+    {
+        matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+    }
+}
+
+/// `path` made absolute through its nearest ancestor that exists, so a destination whose
+/// directories `--mkdir` is yet to create still resolves. What doesn't exist yet can't be a
+/// symlink, so the rest is applied lexically.
+fn resolve_existing_ancestor(path: &Path) -> Option<PathBuf> {
+    let components: Vec<Component> = path.components().collect();
+    for split in (0..=components.len()).rev() {
+        let prefix: PathBuf = components[..split].iter().collect();
+        let prefix: &Path = if prefix.as_os_str().is_empty() { Path::new(".") } else { &prefix };
+        let Ok(mut resolved) = prefix.canonicalize() else { continue };
+        for component in &components[split..] {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                _ => {}
+            }
+        }
+        return Some(resolved);
+    }
+    None
+}
+
+/// Refuses to put the directory `source` somewhere inside itself, where a copy would keep
+/// finding what it is writing. Runs before any missing parent of `dest` is created.
+fn check_not_nested(source: &Path, dest: &Path, verb: &str) -> Result<(), RfmError> {
+    if !source.is_dir() {
+        return Ok(());
+    }
+    if let (Ok(source_dir), Some(parent)) =
+        (source.canonicalize(), resolve_existing_ancestor(parent_dir(dest)))
+        && parent.starts_with(&source_dir)
+    {
+        let e: String = format!("cannot {} {} into itself", verb, source.display());
+        return Err(RfmError::InvalidArgs(e));
+    }
+    Ok(())
+}
+
+/// Moves `from` to `to`, copying and then deleting when they are on different filesystems.
+/// A symlink is moved as the link itself unless `opts.dereference` is set, which moves the
+/// file it points to instead and then removes the link.
+pub fn move_file(
+    from: &str,
+    to: &str,
+    opts: &MoveOptions,
+) -> Result<(), RfmError> {
+    let mut to: String = target_in(from, to);
+    let link: &str = from;
+    let is_link: bool = symlink_metadata(from).is_ok_and(|meta| meta.file_type().is_symlink());
+    let target: Option<String> = if opts.dereference && is_link {
+        let target: PathBuf = Path::new(from).canonicalize().map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => RfmError::NotFound(PathBuf::from(from)),
+            _ => RfmError::Io(e),
+        })?;
+        debug!("{} points to {}, moving that instead", from, target.display());
+        Some(target.to_string_lossy().into_owned())
+    } else {
+        None
+    };
+    let from: &str = target.as_deref().unwrap_or(from);
+    // Renaming a file onto itself does nothing on some platforms and fails on others.
+    if same_file(Path::new(from), Path::new(&to)) {
+        say!(
+            "{} and {} are the same file, nothing to move",
+            paint(Blue, from),
+            paint(Yellow, &to)
+        );
+        return Ok(());
+    }
+    // Only a file, or a directory with nothing to merge into, needs a name of its own.
+    if opts.overwrite == OverwritePolicy::Rename
+        && !(is_real_dir(Path::new(from)) && is_real_dir(Path::new(&to)))
+    {
+        to = free_name(Path::new(&to)).to_string_lossy().into_owned();
+    }
+    let to: &str = &to;
+    check_not_nested(Path::new(from), Path::new(to), "move")?;
+
+    // A directory can't be renamed over one that exists, so the two are merged instead.
+    if is_real_dir(Path::new(from)) && is_real_dir(Path::new(to)) {
+        return merge_move(Path::new(from), Path::new(to), opts);
+    }
+
+    if !check_overwrite(Path::new(to), &opts.overwrite)? {
+        return Ok(());
+    }
+
+    if opts.dry_run {
+        if !Path::new(from).exists() {
+            return Err(RfmError::NotFound(PathBuf::from(from)));
+        }
+        prepare_parent(Path::new(to), opts.mkdir, true)?;
+        if let Some(suffix) = opts.overwrite.backup_suffix() {
+            backup_existing(Path::new(to), suffix, true)?;
+        }
+        dry_run_note(&format!("Would move {} -> {}", from, to));
+        if target.is_some() {
+            dry_run_note(&format!("Would remove the link {}", link));
+        }
+        return Ok(());
+    }
+
+    // Only set the old destination aside once the source is known to be there.
+    let mut backup: Option<PathBuf> = None;
+    if let Some(suffix) = opts.overwrite.backup_suffix() {
+        if !Path::new(from).exists() {
+            return Err(RfmError::NotFound(PathBuf::from(from)));
+        }
+        backup = backup_existing(Path::new(to), suffix, false)?;
+    }
+    prepare_parent(Path::new(to), opts.mkdir, false)?;
+
+    // Rename performs the move when source and destination are on the same filesystem;
+    // across filesystems it fails with EXDEV and the data has to be copied instead.
+    debug!("renaming {} -> {}", from, to);
+    let moved: Result<(), RfmError> = match rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            debug!("{} and {} are on different filesystems, copying instead", from, to);
+            copy_then_delete(Path::new(from), Path::new(to), opts)
+        }
+        result => result.map_err(RfmError::from),
+    };
+
+    // The link would point at nothing once its target has moved.
+    let moved: Result<(), RfmError> = moved.and_then(|()| match &target {
+        Some(_) => remove_file(link).map_err(RfmError::from),
+        None => Ok(()),
+    });
+
+    // A failed move leaves the old destination where it was, unless the copy was kept there.
+    if let Err(e) = moved {
+        if !matches!(e, RfmError::SourceNotRemoved { kept_copy: true, .. }) {
+            restore_backup(Path::new(to), backup);
+        }
+        return Err(with_context(e, &format!("failed to move {} to {}", from, to)));
+    }
+
+    // Keep user-facing success output colorized and explicit.
+    say!(
+        "{}: Moved {} -> {}",
+        paint(Green, "Success"),
+        paint(Blue, from),
+        paint(Yellow, to)
+    );
+    if target.is_some() {
+        say!("Removed the link {}", paint(Blue, link));
+    }
+
+    Ok(())
+}
+
+/// Whether `path` is a directory itself rather than a link to one.
+fn is_real_dir(path: &Path) -> bool {
+    symlink_metadata(path).is_ok_and(|meta| meta.is_dir())
+}
+
+/// Files a merge moved and left where they were, for its closing message.
+#[derive(Default)]
+struct Merged {
+    moved: u64,
+    kept: u64,
+}
+
+/// Moves the directory `from` into the existing directory `to`, merging the two trees under a
+/// single bar for the bytes of the whole source.
+fn merge_move(from: &Path, to: &Path, opts: &MoveOptions) -> Result<(), RfmError> {
+    let pb: ProgressBar = bytes_bar(tree_size(from, opts.dereference, &mut Vec::new())?);
+    let _events: Tracker = track(&pb, "move", &from.to_string_lossy());
+    let mut merged: Merged = Merged::default();
+    let result: Result<(), RfmError> = check_merge(from, to, &opts.overwrite)
+        .and_then(|()| merge_dir(from, to, opts, &pb, &mut merged));
+    pb.finish_and_clear();
+    result.map_err(|e| {
+        with_context(e, &format!("failed to merge {} into {}", from.display(), to.display()))
+    })?;
+
+    let (from, to): (String, String) = (from.display().to_string(), to.display().to_string());
+    if opts.dry_run {
+        dry_run_note(&format!("Would merge {} into {} ({} file(s))", from, to, merged.moved));
+        return Ok(());
+    }
+    say!(
+        "{}: Merged {} into {} ({} file(s))",
+        paint(Green, "Success"),
+        paint(Blue, &from),
+        paint(Yellow, &to),
+        merged.moved
+    );
+    if merged.kept > 0 {
+        notice!(
+            "{} kept {} file(s) in {} that were not moved over existing ones",
+            paint(Yellow, "Note:"),
+            merged.kept,
+            from
+        );
+    }
+    Ok(())
+}
+
+/// Walks the merge of `from` into `to` before anything moves, so it can't stop halfway over a
+/// file and a directory of the same name, or over an existing file `policy` refuses to replace.
+fn check_merge(from: &Path, to: &Path, policy: &OverwritePolicy) -> Result<(), RfmError> {
+    for entry in std::fs::read_dir(from).map_err(|e| entry_error(from, e))? {
+        let entry: std::fs::DirEntry = entry.map_err(|e| entry_error(from, e))?;
+        let source: PathBuf = entry.path();
+        let dest: PathBuf = to.join(entry.file_name());
+        let source_is_dir: bool = entry.file_type().map_err(|e| entry_error(&source, e))?.is_dir();
+        let clash = |what: &str| {
+            let message: String = format!(
+                "cannot merge {} into {}: {} is {}",
+                source.display(),
+                to.display(),
+                dest.display(),
+                what
+            );
+            RfmError::Io(io::Error::new(io::ErrorKind::AlreadyExists, message))
+        };
+        match (source_is_dir, symlink_metadata(&dest).ok().map(|meta| meta.is_dir())) {
+            (true, Some(true)) => check_merge(&source, &dest, policy)?,
+            (true, Some(false)) => return Err(clash("not a directory")),
+            (false, Some(true)) => return Err(clash("a directory")),
+            (false, Some(false)) if *policy == OverwritePolicy::Fail => {
+                check_overwrite(&dest, policy)?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Moves every entry of `from` into `to`: subdirectories on both sides are merged in turn, and
+/// anything else goes through the overwrite policy like a move of its own. `from` is removed
+/// once nothing is left in it.
+fn merge_dir(
+    from: &Path,
+    to: &Path,
+    opts: &MoveOptions,
+    pb: &ProgressBar,
+    merged: &mut Merged,
+) -> Result<(), RfmError> {
+    for entry in std::fs::read_dir(from).map_err(|e| entry_error(from, e))? {
+        let entry: std::fs::DirEntry = entry.map_err(|e| entry_error(from, e))?;
+        let source: PathBuf = entry.path();
+        let mut dest: PathBuf = to.join(entry.file_name());
+        let source_is_dir: bool = entry.file_type().map_err(|e| entry_error(&source, e))?.is_dir();
+        if source_is_dir && is_real_dir(&dest) {
+            merge_dir(&source, &dest, opts, pb, merged)?;
+            continue;
+        }
+
+        let size: u64 = entry_size(&source, opts.dereference)?;
+        let files: u64 = if source_is_dir { count_files(&source)? } else { 1 };
+        if opts.overwrite == OverwritePolicy::Rename {
+            dest = free_name(&dest);
+        }
+        if !check_overwrite(&dest, &opts.overwrite)? {
+            merged.kept += files;
+            pb.inc(size);
+            continue;
+        }
+        if let Some(suffix) = opts.overwrite.backup_suffix() {
+            backup_existing(&dest, suffix, opts.dry_run)?;
+        }
+        if opts.dry_run {
+            dry_run_note(&format!("Would move {} -> {}", source.display(), dest.display()));
+            pb.inc(size);
+        } else {
+            debug!("merging {} -> {}", source.display(), dest.display());
+            move_entry(&source, &dest, size, opts, pb)?;
+        }
+        merged.moved += files;
+    }
+
+    // Anything kept back leaves its directory in place.
+    if !opts.dry_run && std::fs::read_dir(from).map_err(|e| entry_error(from, e))?.next().is_none() {
+        std::fs::remove_dir(from).map_err(|e| entry_error(from, e))?;
+    }
+    Ok(())
+}
+
+/// Moves one entry of a merge, advancing `pb` by its `size` bytes as it goes.
+fn move_entry(
+    source: &Path,
+    dest: &Path,
+    size: u64,
+    opts: &MoveOptions,
+    pb: &ProgressBar,
+) -> Result<(), RfmError> {
+    match rename(source, dest) {
+        Ok(()) => {
+            pb.inc(size);
+            return Ok(());
+        }
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(entry_error(source, e).into()),
+    }
+    copy_entry(source, dest, size, opts, pb).map_err(|e| entry_error(source, e))?;
+    remove_original(source, dest, opts.keep_copy_on_error)
+}
+
+/// Copies one entry of a merge to another filesystem, ahead of removing the original.
+fn copy_entry(
+    source: &Path,
+    dest: &Path,
+    size: u64,
+    opts: &MoveOptions,
+    pb: &ProgressBar,
+) -> io::Result<()> {
+    if relink(source, dest)? {
+        return Ok(());
+    }
+    check_space(dest, size, true)?;
+    if source.is_dir() {
+        copy_tree(source, dest, opts, pb, &mut Vec::new())?;
+    } else {
+        copy_file_with(source, dest, opts.preserve, pb)?;
+    }
+    Ok(())
+}
+
+/// Bytes `tree_size()` counts for `path` as one entry of a tree.
+fn entry_size(path: &Path, dereference: bool) -> io::Result<u64> {
+    let meta: Metadata = entry_metadata(path, dereference).map_err(|e| entry_error(path, e))?;
+    if meta.is_dir() {
+        tree_size(path, dereference, &mut Vec::new())
+    } else {
+        Ok(if meta.is_file() { meta.len() } else { 0 })
+    }
+}
+
+/// Number of entries other than directories in the tree under `dir`.
+fn count_files(dir: &Path) -> io::Result<u64> {
+    let mut count: u64 = 0;
+    for entry in std::fs::read_dir(dir).map_err(|e| entry_error(dir, e))? {
+        let entry: std::fs::DirEntry = entry.map_err(|e| entry_error(dir, e))?;
+        count += match entry.file_type().map_err(|e| entry_error(&entry.path(), e))?.is_dir() {
+            true => count_files(&entry.path())?,
+            false => 1,
+        };
+    }
+    Ok(count)
+}
+
+/// Prefixes `e` with the entry it happened on, so a failure deep in a tree names its file.
+fn entry_error(path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+/// Prefixes an I/O failure with what was being done; the other errors already name their paths.
+fn with_context(e: RfmError, what: &str) -> RfmError {
+    match e {
+        RfmError::Io(e) => RfmError::Io(io::Error::new(e.kind(), format!("{}: {}", what, e))),
+        e => e,
+    }
+}
+
+/// Metadata of `path`, seen through a symlink only when `dereference` is set.
+fn entry_metadata(path: &Path, dereference: bool) -> io::Result<Metadata> {
+    if dereference { metadata(path) } else { symlink_metadata(path) }
+}
+
+/// Notes that a walk is about to go into `dir`, failing if that leads back into one of the
+/// `ancestors` it is already inside. Only a followed symlink can do that, so without
+/// `dereference` nothing is checked.
+fn enter_dir(dir: &Path, dereference: bool, ancestors: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !dereference {
+        return Ok(());
+    }
+    let canonical: PathBuf = dir.canonicalize().map_err(|e| entry_error(dir, e))?;
+    if ancestors.contains(&canonical) {
+        return Err(io::Error::other(format!("symlink loop detected at {}", dir.display())));
+    }
+    ancestors.push(canonical);
+    Ok(())
+}
+
+/// Undoes `enter_dir()` once the walk is done with a directory.
+fn leave_dir(dereference: bool, ancestors: &mut Vec<PathBuf>) {
+    if dereference {
+        ancestors.pop();
+    }
+}
+
+/// Total size of the files `copy_tree()` would copy out of `from`, inside the directories
+/// `ancestors` as `enter_dir()` keeps them.
+fn tree_size(from: &Path, dereference: bool, ancestors: &mut Vec<PathBuf>) -> io::Result<u64> {
+    enter_dir(from, dereference, ancestors)?;
+    let mut total: u64 = 0;
+    for entry in std::fs::read_dir(from).map_err(|e| entry_error(from, e))? {
+        let path: PathBuf = entry.map_err(|e| entry_error(from, e))?.path();
+        let meta: Metadata = entry_metadata(&path, dereference).map_err(|e| entry_error(&path, e))?;
+        if meta.is_dir() {
+            total += tree_size(&path, dereference, ancestors)?;
+        } else if meta.is_file() {
+            total += meta.len();
+        }
+    }
+    leave_dir(dereference, ancestors);
+    Ok(total)
+}
+
+/// Bytes copying `from` writes: its length, or the size of every file beneath a directory.
+fn source_size(from: &Path, dereference: bool) -> io::Result<u64> {
+    if from.is_dir() {
+        tree_size(from, dereference, &mut Vec::new())
+    } else {
+        Ok(metadata(from)?.len())
+    }
+}
+
+/// Copies the directory `from` to `to` under a single bar for the `total` bytes of the tree.
+fn copy_tree_with_progress(
+    from: &Path,
+    to: &Path,
+    total: u64,
+    opts: &MoveOptions,
+) -> io::Result<u64> {
+    let pb: ProgressBar = bytes_bar(total);
+    let _events: Tracker = track(&pb, "copy", &from.to_string_lossy());
+    let copied: io::Result<u64> = copy_tree(from, to, opts, &pb, &mut Vec::new());
+    pb.finish_and_clear();
+    copied
+}
+
+/// Recreates the directory `from` at `to`, copying every file beneath it, and returns the
+/// number of bytes copied. On Unix, symlinks are recreated rather than followed unless
+/// `dereference` is set, and a followed symlink back into a directory being copied is an
+/// error rather than a copy without end. Errors name the entry they happened on.
+fn copy_tree(
+    from: &Path,
+    to: &Path,
+    opts: &MoveOptions,
+    pb: &ProgressBar,
+    ancestors: &mut Vec<PathBuf>,
+) -> io::Result<u64> {
+    let source_meta: Metadata = metadata(from).map_err(|e| entry_error(from, e))?;
+    enter_dir(from, opts.dereference, ancestors)?;
+    create_dir_all(to).map_err(|e| entry_error(to, e))?;
+    let mut copied: u64 = 0;
+
+    for entry in std::fs::read_dir(from).map_err(|e| entry_error(from, e))? {
+        let entry: std::fs::DirEntry = entry.map_err(|e| entry_error(from, e))?;
+        let source: PathBuf = entry.path();
+        let dest: PathBuf = to.join(entry.file_name());
+        let kind: std::fs::FileType = entry_metadata(&source, opts.dereference)
+            .map_err(|e| entry_error(&source, e))?
+            .file_type();
+
+        if kind.is_dir() {
+            copied += copy_tree(&source, &dest, opts, pb, ancestors)?;
+        } else if kind.is_symlink() && cfg!(unix) {
+            #[cfg(unix)]
+            std::fs::read_link(&source)
+                .and_then(|target| std::os::unix::fs::symlink(target, &dest))
+                .map_err(|e| entry_error(&source, e))?;
+        } else {
+            debug!("copying {} -> {}", source.display(), dest.display());
+            copied += copy_file_with(&source, &dest, opts.preserve, pb)
+                .map_err(|e| entry_error(&source, e))?;
+        }
+    }
+
+    leave_dir(opts.dereference, ancestors);
+
+    // Timestamps go on once the contents stop changing them.
+    #[cfg(unix)]
+    if opts.preserve {
+        File::open(to)
+            .and_then(|dir| copy_metadata(&source_meta, &dir))
+            .map_err(|e| entry_error(to, e))?;
+    }
+
+    // Applied last so a read-only source directory doesn't block filling in its copy.
+    let permissions: Permissions = source_meta.permissions();
+    std::fs::set_permissions(to, permissions).map_err(|e| entry_error(to, e))?;
+    Ok(copied)
+}
+
+/// Copies the file or directory `from` to `to`, leaving the source in place.
+pub fn copy_path(
+    from: &str,
+    to: &str,
+    opts: &MoveOptions,
+) -> Result<(), RfmError> {
+    let mut to: String = target_in(from, to);
+    // Creating the destination would truncate the very file being read.
+    if same_file(Path::new(from), Path::new(&to)) {
+        say!(
+            "{} and {} are the same file, nothing to copy",
+            paint(Blue, from),
+            paint(Yellow, &to)
+        );
+        return Ok(());
+    }
+    if opts.overwrite == OverwritePolicy::Rename {
+        to = free_name(Path::new(&to)).to_string_lossy().into_owned();
+    }
+    let to: &str = &to;
+    if !check_overwrite(Path::new(to), &opts.overwrite)? {
+        return Ok(());
+    }
+
+    let source: &Path = Path::new(from);
+    if !source.exists() {
+        return Err(RfmError::NotFound(source.to_path_buf()));
+    }
+
+    check_not_nested(source, Path::new(to), "copy")?;
+
+    prepare_parent(Path::new(to), opts.mkdir, opts.dry_run)?;
+    // Checked before the backup is taken, so a copy that won't fit changes nothing.
+    let replacing: bool = opts.overwrite.backup_suffix().is_none();
+    let size: io::Result<u64> = source_size(source, opts.dereference)
+        .and_then(|size| check_space(Path::new(to), size, replacing).map(|()| size));
+    let size: u64 =
+        size.map_err(|e| with_context(e.into(), &format!("failed to copy {} to {}", from, to)))?;
+    let backup: Option<PathBuf> = match opts.overwrite.backup_suffix() {
+        Some(suffix) => backup_existing(Path::new(to), suffix, opts.dry_run)?,
+        None => None,
+    };
+
+    if opts.dry_run {
+        let kind: &str = if source.is_dir() { "directory" } else { "file" };
+        dry_run_note(&format!("Would copy {} {} -> {}", kind, from, to));
+        return Ok(());
+    }
+
+    debug!("copying {} -> {}", from, to);
+    let copied: io::Result<u64> = if source.is_dir() {
+        copy_tree_with_progress(source, Path::new(to), size, opts)
+    } else {
+        copy_file(source, Path::new(to), opts.preserve)
+    };
+
+    match copied {
+        Ok(bytes) => {
+            say!(
+                "{}: Copied {} -> {} ({} bytes)",
+                paint(Green, "Success"),
+                paint(Blue, from),
+                paint(Yellow, to),
+                bytes
+            );
+            Ok(())
+        }
+        Err(e) => {
+            restore_backup(Path::new(to), backup);
+            Err(with_context(e.into(), &format!("failed to copy {} to {}", from, to)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own under the system temp directory.
+    fn scratch(name: &str) -> PathBuf {
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("rfm-transfer-{}-{}", name, std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn options(mkdir: bool) -> MoveOptions {
+        MoveOptions {
+            overwrite: OverwritePolicy::Fail,
+            dry_run: false,
+            preserve: true,
+            dereference: false,
+            mkdir,
+            keep_copy_on_error: false,
+        }
+    }
+
+    fn path(path: &Path) -> &str {
+        path.to_str().unwrap()
+    }
+
+    #[test]
+    fn moving_a_file_onto_itself_leaves_it_alone() {
+        let dir: PathBuf = scratch("self");
+        let file: PathBuf = dir.join("file.txt");
+        std::fs::write(&file, "contents").unwrap();
+
+        move_file(path(&file), path(&file), &options(false)).unwrap();
+        // Spelled differently, it is still the same file.
+        let dotted: PathBuf = dir.join(".").join("file.txt");
+        move_file(path(&file), path(&dotted), &options(false)).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "contents");
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn moving_a_directory_into_itself_is_refused() {
+        let dir: PathBuf = scratch("nested-move");
+        let source: PathBuf = dir.join("d");
+        create_dir_all(source.join("sub")).unwrap();
+
+        let result: Result<(), RfmError> =
+            move_file(path(&source), path(&source.join("sub").join("d")), &options(false));
+        assert!(matches!(result, Err(RfmError::InvalidArgs(_))));
+        assert!(source.join("sub").is_dir());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn copying_a_directory_into_a_missing_subdirectory_of_itself_is_refused() {
+        let dir: PathBuf = scratch("nested-copy");
+        let source: PathBuf = dir.join("d");
+        create_dir_all(&source).unwrap();
+        std::fs::write(source.join("file"), "contents").unwrap();
+
+        // `new` doesn't exist yet, so only its ancestor `d` can say where it will be.
+        let dest: PathBuf = source.join("new").join("x");
+        let result: Result<(), RfmError> = copy_path(path(&source), path(&dest), &options(true));
+        assert!(matches!(result, Err(RfmError::InvalidArgs(_))));
+        assert!(!source.join("new").exists());
+        // A `..` in the missing part still leads back into the source.
+        let dest: PathBuf = source.join("new").join("..").join("again");
+        let result: Result<(), RfmError> = copy_path(path(&source), path(&dest), &options(true));
+        assert!(matches!(result, Err(RfmError::InvalidArgs(_))));
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copying_through_a_symlink_loop_is_refused() {
+        let dir: PathBuf = scratch("loop");
+        let source: PathBuf = dir.join("d");
+        create_dir_all(&source).unwrap();
+        std::fs::write(source.join("file"), "contents").unwrap();
+        // Followed, `loop` leads back to `d` again through its parent.
+        std::os::unix::fs::symlink("..", source.join("loop")).unwrap();
+
+        let opts: MoveOptions = MoveOptions { dereference: true, ..options(false) };
+        let result: Result<(), RfmError> =
+            copy_path(path(&source), path(&dir.join("out")), &opts);
+        match result {
+            Err(RfmError::Io(e)) => assert!(e.to_string().contains("symlink loop detected"), "{e}"),
+            other => panic!("expected a symlink loop error, got {:?}", other),
+        }
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_replacement_puts_the_backup_back() {
+        let dir: PathBuf = scratch("restore");
+        let dest: PathBuf = dir.join("file");
+        std::fs::write(&dest, "old").unwrap();
+        let backup: Option<PathBuf> = backup_existing(&dest, "~", false).unwrap();
+        assert!(!dest.exists());
+
+        // What a failed copy left behind goes, and the backup takes its place again.
+        std::fs::write(&dest, "par").unwrap();
+        restore_backup(&dest, backup);
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), "old");
+        assert!(!dir.join("file~").exists());
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_preserving_copy_keeps_the_setuid_bit() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir: PathBuf = scratch("setuid");
+        let source: PathBuf = dir.join("tool");
+        std::fs::write(&source, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&source, Permissions::from_mode(0o4755)).unwrap();
+
+        let dest: PathBuf = dir.join("copy");
+        copy_path(path(&source), path(&dest), &options(false)).unwrap();
+        assert_eq!(metadata(&dest).unwrap().permissions().mode() & 0o7777, 0o4755);
+        remove_dir_all(&dir).unwrap();
+    }
+}